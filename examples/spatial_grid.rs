@@ -0,0 +1,118 @@
+// Compares finding every pair of overlapping tanks by checking every tank against every other, with
+// finding them through the spatial grid the game uses. Run it with:
+//
+//     cargo run --release --example spatial_grid
+//
+// The grid times include rebuilding it, as the game does every fixed tick. Each count is run twice:
+// once with every tank in the arena, which is what the game does as keep_tanks_on_screen holds them
+// in it, and once over an area that grows with the count, so the tanks are only as crowded as CROWD
+// tanks in the arena. The first is the one that matters for the game, and the second shows how the
+// grid does when tanks are spread out.
+
+use bevy::prelude::*;
+use rand::Rng;
+use std::time::{Duration, Instant};
+
+// The game uses more of the grid than this does
+#[allow(dead_code)]
+#[path = "../src/utils/grid.rs"]
+mod grid;
+use grid::SpatialGrid;
+
+// The same as in the game
+const ARENA_SIZE: Vec2 = Vec2::new(1600.0, 1200.0);
+const CELL_SIZE: f32 = 64.0;
+const TANK_RADII: [f32; 3] = [16.0, 20.0, 25.0];
+
+// How crowded the tanks are in the spread out runs
+const CROWD: usize = 250;
+
+const TANK_COUNTS: [usize; 7] = [50, 100, 250, 500, 1000, 2000, 4000];
+// Each count is timed this many times, and the fastest run kept
+const RUNS: usize = 20;
+
+struct Circle {
+    position: Vec2,
+    radius: f32,
+}
+
+fn random_tanks(count: usize, area: Vec2) -> Vec<Circle> {
+    let mut rng = rand::thread_rng();
+    (0..count)
+        .map(|_| Circle {
+            position: Vec2::new(rng.gen_range(0.0..area.x), rng.gen_range(0.0..area.y)),
+            radius: TANK_RADII[rng.gen_range(0..TANK_RADII.len())],
+        })
+        .collect()
+}
+
+fn overlapping(a: &Circle, b: &Circle) -> bool {
+    a.position.distance(b.position) < a.radius + b.radius
+}
+
+fn brute_force_pairs(tanks: &[Circle]) -> usize {
+    let mut pairs = 0;
+    for i in 0..tanks.len() {
+        for j in (i + 1)..tanks.len() {
+            if overlapping(&tanks[i], &tanks[j]) {
+                pairs += 1;
+            }
+        }
+    }
+    pairs
+}
+
+fn grid_pairs(grid: &mut SpatialGrid, tanks: &[Circle]) -> usize {
+    grid.clear();
+    for (i, tank) in tanks.iter().enumerate() {
+        grid.insert(Entity::from_raw(i as u32), tank.position, tank.radius);
+    }
+    let mut pairs = 0;
+    for (i, tank) in tanks.iter().enumerate() {
+        for entry in grid.nearby(tank.position, tank.radius) {
+            let j = entry.entity.index() as usize;
+            if j > i && overlapping(tank, &tanks[j]) {
+                pairs += 1;
+            }
+        }
+    }
+    pairs
+}
+
+// Runs `find_pairs` RUNS times, returning how many pairs it found and its fastest time
+fn time(mut find_pairs: impl FnMut() -> usize) -> (usize, Duration) {
+    let mut pairs = 0;
+    let mut fastest = Duration::MAX;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        pairs = std::hint::black_box(find_pairs());
+        fastest = fastest.min(start.elapsed());
+    }
+    (pairs, fastest)
+}
+
+fn main() {
+    let mut grid = SpatialGrid::new(CELL_SIZE);
+    println!(
+        "{:>8} {:>6} {:>7} {:>14} {:>14} {:>8}",
+        "area", "tanks", "pairs", "brute force", "grid", "speedup"
+    );
+    for count in TANK_COUNTS {
+        let spread = ARENA_SIZE * (count as f32 / CROWD as f32).sqrt();
+        for (name, area) in [("arena", ARENA_SIZE), ("spread", spread)] {
+            let tanks = random_tanks(count, area);
+            let (brute_force, brute_force_time) = time(|| brute_force_pairs(&tanks));
+            let (gridded, grid_time) = time(|| grid_pairs(&mut grid, &tanks));
+            assert_eq!(brute_force, gridded, "the grid missed or doubled up a pair");
+            println!(
+                "{:>8} {:>6} {:>7} {:>14.1?} {:>14.1?} {:>7.1}x",
+                name,
+                count,
+                brute_force,
+                brute_force_time,
+                grid_time,
+                brute_force_time.as_secs_f64() / grid_time.as_secs_f64()
+            );
+        }
+    }
+}
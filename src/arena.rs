@@ -0,0 +1,42 @@
+use bevy::prelude::*;
+
+// The playing area, centred on the origin. It can be bigger than the window, in which case the
+// camera follows the player around it (see camera.rs).
+pub const ARENA_WIDTH: f32 = 1600.0;
+pub const ARENA_HEIGHT: f32 = 1200.0;
+// How thick the walls around the arena are for collisions. They only need to be thick enough that
+// nothing can pass all the way through one in a single tick.
+const WALL_THICKNESS: f32 = 200.0;
+
+#[derive(Resource)]
+pub struct Arena {
+    pub size: Vec2,
+}
+
+impl Default for Arena {
+    fn default() -> Arena {
+        Arena {
+            size: Vec2::new(ARENA_WIDTH, ARENA_HEIGHT),
+        }
+    }
+}
+
+impl Arena {
+    // Half of the arena's size, which is also the position of its top right corner
+    pub fn half_size(&self) -> Vec2 {
+        self.size / 2.0
+    }
+
+    // The walls around the edge of the arena, as solid rectangles just outside it. The ones along
+    // the top and bottom cover the corners.
+    pub fn walls(&self) -> [Rect; 4] {
+        let edge = self.half_size();
+        let outer = edge + WALL_THICKNESS;
+        [
+            Rect::new(-outer.x, -outer.y, outer.x, -edge.y),
+            Rect::new(-outer.x, edge.y, outer.x, outer.y),
+            Rect::new(-outer.x, -edge.y, -edge.x, edge.y),
+            Rect::new(edge.x, -edge.y, outer.x, edge.y),
+        ]
+    }
+}
@@ -0,0 +1,81 @@
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+use crate::arena::Arena;
+use crate::camera_effects::CameraEffects;
+use crate::tanks::Player;
+
+// A camera that follows the player around arenas that are bigger than the screen. The player can
+// move freely within a dead zone in the middle of the screen without the camera moving, and the
+// camera never shows anything outside of the arena.
+
+// Half of the size of the dead zone, in world units
+const DEAD_ZONE: Vec2 = Vec2::new(120.0, 80.0);
+// How quickly the camera catches up with where it wants to be, per second
+const CAMERA_SMOOTHING: f32 = 6.0;
+
+#[derive(Component)]
+pub struct FollowCamera;
+
+// The part of the world the camera can currently see
+pub fn visible_area(transform: &Transform, projection: &OrthographicProjection) -> Rect {
+    Rect::from_center_size(
+        transform.translation.truncate() + projection.area.center(),
+        projection.area.size(),
+    )
+}
+
+pub fn follow_player(
+    time: Res<Time>,
+    arena: Res<Arena>,
+    effects: Res<CameraEffects>,
+    players: Query<&Transform, (With<Player>, Without<FollowCamera>)>,
+    mut cameras: Query<(&mut Transform, &OrthographicProjection), With<FollowCamera>>,
+) {
+    let Ok((mut camera, projection)) = cameras.get_single_mut() else {
+        return;
+    };
+    // Follow from where the camera would be without any shake or kick (see camera_effects.rs)
+    let current = camera.translation.truncate() - effects.offset;
+
+    // Only move once the player leaves the dead zone, and then only enough to bring them back to
+    // its edge. With no player, the camera stays where it is.
+    let mut target = current;
+    if let Ok(player) = players.get_single() {
+        let offset = player.translation.truncate() - current;
+        target += offset - offset.clamp(-DEAD_ZONE, DEAD_ZONE);
+    }
+
+    // Keep the view inside the arena. If the arena is smaller than the view in either direction,
+    // centre on it in that direction instead.
+    let room = (arena.half_size() - projection.area.size() / 2.0).max(Vec2::ZERO);
+    target = target.clamp(-room, room);
+
+    let smoothing = (CAMERA_SMOOTHING * time.delta_seconds()).min(1.0);
+    let position = current.lerp(target, smoothing);
+    camera.translation = (position + effects.offset).extend(camera.translation.z);
+}
+
+// Where the mouse cursor is in the world, or None when it is outside of the window. This goes
+// through the camera, so it stays right however the window is sized or scaled and wherever the
+// camera has moved to.
+#[derive(Resource, Default)]
+pub struct CursorWorldPosition(pub Option<Vec2>);
+
+pub fn update_cursor_world_position(
+    mut cursor: ResMut<CursorWorldPosition>,
+    primary_window: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &Transform), With<FollowCamera>>,
+) {
+    let (Ok(window), Ok((camera, transform))) = (primary_window.get_single(), cameras.get_single())
+    else {
+        cursor.0 = None;
+        return;
+    };
+    // The camera's GlobalTransform isn't updated until the end of the frame, so it is rebuilt from
+    // its Transform to take into account any movement from follow_player this frame
+    let camera_transform = GlobalTransform::from(*transform);
+    cursor.0 = window
+        .cursor_position()
+        .and_then(|position| camera.viewport_to_world_2d(&camera_transform, position));
+}
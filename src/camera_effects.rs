@@ -0,0 +1,111 @@
+use bevy::prelude::*;
+
+use crate::camera::FollowCamera;
+use crate::settings::Settings;
+
+// Screen shake, camera kick and hit-stop, to give combat some weight.
+//
+// Shake is driven by "trauma": events add trauma, which slowly wears off, and the camera shakes by
+// trauma squared so that small hits barely register while big ones really rattle the screen. Kick
+// nudges the camera in a direction and then eases back. Both are offsets on top of wherever
+// follow_player puts the camera. Hit-stop briefly slows time to a crawl when something dies.
+
+// How much trauma each event adds. Trauma is capped at 1.0.
+pub const FIRE_TRAUMA: f32 = 0.1;
+pub const DAMAGE_TRAUMA: f32 = 0.45;
+pub const SHIELD_HIT_TRAUMA: f32 = 0.25;
+pub const EXPLOSION_TRAUMA: f32 = 0.6;
+// How far the camera is kicked when the player fires
+pub const FIRE_KICK: f32 = 5.0;
+// How long kills freeze the game for, in real seconds
+pub const KILL_HIT_STOP: f32 = 0.08;
+
+// How much trauma wears off per second
+const TRAUMA_DECAY: f32 = 1.2;
+// The furthest the camera moves and rotates at full trauma
+const MAX_SHAKE_OFFSET: f32 = 14.0;
+const MAX_SHAKE_ANGLE: f32 = 0.04;
+// How fast the shake wobbles
+const SHAKE_FREQUENCY: f32 = 25.0;
+// How quickly a kick eases back, per second
+const KICK_RECOVERY: f32 = 12.0;
+// How fast time runs during hit-stop
+const HIT_STOP_SPEED: f32 = 0.05;
+
+#[derive(Resource, Default)]
+pub struct CameraEffects {
+    trauma: f32,
+    kick: Vec2,
+    // Real seconds of hit-stop left
+    hit_stop: f32,
+    // The offset currently applied to the camera, which follow_player takes back off
+    pub offset: Vec2,
+}
+
+impl CameraEffects {
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).min(1.0);
+    }
+
+    pub fn kick(&mut self, direction: Vec2, distance: f32) {
+        self.kick += direction.normalize_or_zero() * distance;
+    }
+
+    // Overlapping hit-stops don't add up, or a multi-kill would freeze the game for ages
+    pub fn hit_stop(&mut self, duration: f32) {
+        self.hit_stop = self.hit_stop.max(duration);
+    }
+}
+
+// Smooth pseudo-random wobble between -1 and 1. Each seed gives a different wobble, so the x, y
+// and rotation shakes don't move in step.
+fn wobble(t: f32, seed: f32) -> f32 {
+    ((t + seed).sin() + (t * 2.3 + seed * 1.7).sin() * 0.6 + (t * 4.1 + seed * 2.9).sin() * 0.3)
+        / 1.9
+}
+
+pub fn apply_camera_effects(
+    real_time: Res<Time<Real>>,
+    settings: Res<Settings>,
+    mut effects: ResMut<CameraEffects>,
+    mut cameras: Query<&mut Transform, With<FollowCamera>>,
+) {
+    // Effects run on real time, so that they still wear off during hit-stop
+    let delta = real_time.delta_seconds();
+    effects.trauma = (effects.trauma - TRAUMA_DECAY * delta).max(0.0);
+    effects.kick *= (1.0 - KICK_RECOVERY * delta).max(0.0);
+
+    let strength = settings.screen_shake.max(0.0);
+    let shake = effects.trauma * effects.trauma * strength;
+    let t = real_time.elapsed_seconds() * SHAKE_FREQUENCY;
+    let offset = Vec2::new(wobble(t, 0.0), wobble(t, 10.0)) * MAX_SHAKE_OFFSET * shake
+        + effects.kick * strength;
+    let angle = wobble(t, 20.0) * MAX_SHAKE_ANGLE * shake;
+
+    for mut camera in cameras.iter_mut() {
+        camera.translation += (offset - effects.offset).extend(0.0);
+        camera.rotation = Quat::from_rotation_z(angle);
+    }
+    effects.offset = offset;
+}
+
+// Slows the game down while hit-stop is active. Music and the camera effects themselves carry on.
+pub fn update_hit_stop(
+    real_time: Res<Time<Real>>,
+    settings: Res<Settings>,
+    mut effects: ResMut<CameraEffects>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+) {
+    if !settings.hit_stop {
+        effects.hit_stop = 0.0;
+    }
+    let speed = if effects.hit_stop > 0.0 {
+        HIT_STOP_SPEED
+    } else {
+        1.0
+    };
+    if virtual_time.relative_speed() != speed {
+        virtual_time.set_relative_speed(speed);
+    }
+    effects.hit_stop = (effects.hit_stop - real_time.delta_seconds()).max(0.0);
+}
@@ -0,0 +1,162 @@
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::*;
+use dot32_intro::{FADE, LENGTH};
+
+use crate::sound::{play_countdown_beep, play_countdown_go};
+use crate::{AppState, MUTE};
+
+// Every round starts with a "3, 2, 1, GO!" countdown. Nobody can drive, aim or fire until it is
+// over, which is handled where the simulation is scheduled (see main.rs) rather than in each system.
+// The countdown only runs while the game is unpaused, and the first one waits for the intro.
+
+// Seconds counted down before the round starts
+pub const COUNTDOWN_SECONDS: f32 = 3.0;
+// How long "GO!" stays up once the round has started
+const GO_SECONDS: f32 = 0.75;
+const COUNTDOWN_FONT_SIZE: f32 = 120.0;
+
+#[derive(States, Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub enum RoundState {
+    #[default]
+    Countdown,
+    Fighting,
+}
+
+#[derive(Resource)]
+pub struct Countdown {
+    // Seconds until the round starts. It keeps going below zero while "GO!" is shown.
+    pub remaining: f32,
+    // The number last shown, or 0 for "GO!", so each one beeps once
+    announced: Option<i32>,
+}
+
+impl Default for Countdown {
+    fn default() -> Countdown {
+        Countdown {
+            remaining: COUNTDOWN_SECONDS,
+            announced: None,
+        }
+    }
+}
+
+impl Countdown {
+    fn is_finished(&self) -> bool {
+        self.remaining <= -GO_SECONDS
+    }
+
+    // The number to show, or 0 for "GO!"
+    fn number(&self) -> i32 {
+        self.remaining.ceil().max(0.0) as i32
+    }
+}
+
+#[derive(Component)]
+pub struct CountdownText;
+
+// Starts a new round. The countdown is run by run_countdown from here.
+pub fn start_countdown(mut countdown: ResMut<Countdown>) {
+    *countdown = Countdown::default();
+}
+
+// The dot32 intro is shown over the game when it starts, so the first countdown waits for it
+pub fn intro_finished(time: Res<Time<Real>>) -> bool {
+    LENGTH + FADE < time.elapsed_seconds()
+}
+
+pub fn run_countdown(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut countdown: ResMut<Countdown>,
+    round: Res<State<RoundState>>,
+    mut next_round: ResMut<NextState<RoundState>>,
+) {
+    if countdown.is_finished() {
+        return;
+    }
+    countdown.remaining -= time.delta_seconds();
+
+    let number = countdown.number();
+    if number == 0 && *round.get() == RoundState::Countdown {
+        next_round.set(RoundState::Fighting);
+    }
+    if countdown.announced == Some(number) {
+        return;
+    }
+    countdown.announced = Some(number);
+    if !MUTE {
+        // Goofy ahh work around to world being exclusive
+        if number == 0 {
+            commands.add(|world: &mut World| world.run_system_once(play_countdown_go))
+        } else {
+            commands.add(|world: &mut World| world.run_system_once(play_countdown_beep))
+        }
+    }
+}
+
+pub fn spawn_countdown_text(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                // Shown by update_countdown_text while the countdown is running
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            CountdownText,
+            Name::new("Countdown"),
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/PT_Sans/PTSans-Regular.ttf"),
+                    font_size: COUNTDOWN_FONT_SIZE,
+                    color: Color::srgb(0.9, 0.9, 0.9),
+                },
+            ));
+        });
+}
+
+// Each number fades out over its second, but stays readable, and "GO!" fades out completely once
+// the round starts. Nothing is shown over the intro, the pause menu, or once the round is going.
+pub fn update_countdown_text(
+    countdown: Res<Countdown>,
+    state: Res<State<AppState>>,
+    time: Res<Time<Real>>,
+    mut nodes: Query<(&mut Visibility, &Children), With<CountdownText>>,
+    mut texts: Query<&mut Text>,
+) {
+    let running =
+        *state.get() == AppState::Game && intro_finished(time) && !countdown.is_finished();
+    let number = countdown.number();
+    let (value, alpha) = if number == 0 {
+        (
+            "GO!".to_string(),
+            (1.0 + countdown.remaining / GO_SECONDS).max(0.0),
+        )
+    } else {
+        let fade = countdown.remaining - (number - 1) as f32;
+        (number.to_string(), fade.max(0.3))
+    };
+
+    for (mut visibility, children) in nodes.iter_mut() {
+        if !running {
+            visibility.set_if_neq(Visibility::Hidden);
+            continue;
+        }
+        visibility.set_if_neq(Visibility::Inherited);
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.sections[0].value.clone_from(&value);
+                text.sections[0].style.color.set_alpha(alpha.min(1.0));
+            }
+        }
+    }
+}
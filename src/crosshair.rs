@@ -0,0 +1,93 @@
+use bevy::prelude::*;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+use bevy::window::PrimaryWindow;
+
+use crate::camera::CursorWorldPosition;
+use crate::AppState;
+
+// A crosshair drawn in the world where the player is aiming, in place of the system cursor. The
+// system cursor comes back while the game is paused and while hovering over a button.
+
+const CROSSHAIR_RADIUS: f32 = 9.0;
+const CROSSHAIR_THICKNESS: f32 = 1.5;
+// The ticks start just inside the ring and stick out past it
+const CROSSHAIR_TICK_LENGTH: f32 = 6.0;
+const CROSSHAIR_COLOUR: Color = Color::srgba(1.0, 1.0, 1.0, 0.85);
+// In front of everything else in the world
+const CROSSHAIR_Z: f32 = 50.0;
+
+#[derive(Component)]
+pub struct Crosshair;
+
+pub fn spawn_crosshair(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let material = materials.add(CROSSHAIR_COLOUR);
+    commands
+        .spawn((
+            SpatialBundle {
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            Crosshair,
+            Name::new("Crosshair"),
+        ))
+        .with_children(|parent| {
+            parent.spawn(MaterialMesh2dBundle {
+                mesh: Mesh2dHandle(meshes.add(Annulus::new(
+                    CROSSHAIR_RADIUS - CROSSHAIR_THICKNESS,
+                    CROSSHAIR_RADIUS,
+                ))),
+                material: material.clone(),
+                ..default()
+            });
+            let tick = Mesh2dHandle(
+                meshes.add(Rectangle::new(CROSSHAIR_TICK_LENGTH, CROSSHAIR_THICKNESS)),
+            );
+            for direction in [Vec2::X, Vec2::Y, Vec2::NEG_X, Vec2::NEG_Y] {
+                let centre = direction * (CROSSHAIR_RADIUS + CROSSHAIR_TICK_LENGTH / 2.0 - 2.0);
+                parent.spawn(MaterialMesh2dBundle {
+                    mesh: tick.clone(),
+                    material: material.clone(),
+                    transform: Transform {
+                        translation: centre.extend(0.0),
+                        rotation: Quat::from_rotation_z(direction.to_angle()),
+                        ..default()
+                    },
+                    ..default()
+                });
+            }
+        });
+}
+
+pub fn update_crosshair(
+    cursor: Res<CursorWorldPosition>,
+    state: Res<State<AppState>>,
+    buttons: Query<&Interaction, With<Button>>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut crosshairs: Query<(&mut Transform, &mut Visibility), With<Crosshair>>,
+) {
+    let over_button = buttons
+        .iter()
+        .any(|interaction| *interaction != Interaction::None);
+    let aiming = *state.get() == AppState::Game && !over_button;
+
+    for (mut transform, mut visibility) in crosshairs.iter_mut() {
+        match cursor.0 {
+            Some(position) if aiming => {
+                transform.translation = position.extend(CROSSHAIR_Z);
+                *visibility = Visibility::Visible;
+            }
+            _ => *visibility = Visibility::Hidden,
+        }
+    }
+
+    if let Ok(mut window) = windows.get_single_mut() {
+        // Only touch the window when something changes, so it isn't marked as changed every frame
+        if window.cursor.visible == aiming {
+            window.cursor.visible = !aiming;
+        }
+    }
+}
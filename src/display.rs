@@ -0,0 +1,97 @@
+use bevy::prelude::*;
+use bevy::render::camera::Viewport;
+use bevy::render::view::RenderLayers;
+use bevy::window::{PrimaryWindow, WindowMode};
+
+use crate::camera::FollowCamera;
+use crate::{GAME_HEIGHT, GAME_WIDTH};
+
+// The window can be resized or made fullscreen. The game always shows a GAME_WIDTH x GAME_HEIGHT
+// view of the world, scaled up or down as much as fits in the window, with black bars filling
+// whatever is left over. The UI is scaled by the same amount, so it keeps the same size relative
+// to the game.
+
+// Nothing is drawn on this layer, so the letterbox camera only clears the window
+const LETTERBOX_LAYER: usize = 1;
+
+// A camera that draws nothing, behind the main camera, which paints the bars black
+#[derive(Component)]
+pub struct LetterboxCamera;
+
+pub fn spawn_letterbox_camera(mut commands: Commands) {
+    commands.spawn((
+        Camera2dBundle {
+            camera: Camera {
+                order: -1,
+                clear_color: ClearColorConfig::Custom(Color::BLACK),
+                ..default()
+            },
+            ..default()
+        },
+        RenderLayers::layer(LETTERBOX_LAYER),
+        LetterboxCamera,
+        Name::new("Letterbox camera"),
+    ));
+}
+
+// How much the game is scaled up by to fit in a window of this size
+pub fn game_scale(window_size: Vec2) -> f32 {
+    (window_size.x / GAME_WIDTH).min(window_size.y / GAME_HEIGHT)
+}
+
+// Fits the main camera's viewport to the biggest GAME_WIDTH:GAME_HEIGHT rectangle that fits in
+// the middle of the window whenever the window changes size
+pub fn fit_viewport_to_window(
+    mut ui_scale: ResMut<UiScale>,
+    windows: Query<&Window, (With<PrimaryWindow>, Changed<Window>)>,
+    mut cameras: Query<&mut Camera, With<FollowCamera>>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let physical_size = Vec2::new(
+        window.physical_width() as f32,
+        window.physical_height() as f32,
+    );
+    // A minimised window has no size, and a zero sized viewport crashes the renderer
+    if physical_size.min_element() < 1.0 {
+        return;
+    }
+
+    let scale = game_scale(physical_size);
+    let viewport_size = (Vec2::new(GAME_WIDTH, GAME_HEIGHT) * scale).max(Vec2::ONE);
+    let viewport_position = (physical_size - viewport_size) / 2.0;
+
+    for mut camera in cameras.iter_mut() {
+        camera.viewport = Some(Viewport {
+            physical_position: viewport_position.as_uvec2(),
+            physical_size: viewport_size.as_uvec2(),
+            ..default()
+        });
+    }
+
+    // UiScale is in logical pixels, so the window's scale factor has to be taken back out
+    let logical_scale = game_scale(Vec2::new(window.width(), window.height()));
+    if ui_scale.0 != logical_scale {
+        ui_scale.0 = logical_scale;
+    }
+}
+
+// F11 or Alt+Enter switches between windowed and fullscreen
+pub fn toggle_fullscreen(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let alt = keyboard_input.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]);
+    if !(keyboard_input.just_pressed(KeyCode::F11)
+        || alt && keyboard_input.just_pressed(KeyCode::Enter))
+    {
+        return;
+    }
+    for mut window in windows.iter_mut() {
+        window.mode = match window.mode {
+            WindowMode::Windowed => WindowMode::BorderlessFullscreen,
+            _ => WindowMode::Windowed,
+        };
+    }
+}
@@ -0,0 +1,84 @@
+use bevy::prelude::*;
+
+use crate::utils::angle_between;
+
+// "Tank controls": instead of moving straight in the direction pressed, a tank with a TankDrive
+// turns its hull left and right and drives forwards or backwards along it. The player and the AI
+// both drive through the same model, so they handle the same way.
+
+// How quickly a tank starts turning, in radians per tick per tick
+const TURN_ACCELERATION: f32 = 0.02;
+// The fraction of its turning speed a tank keeps each tick, so it stops turning soon after the
+// player lets go
+const TURN_FRICTION: f32 = 0.75;
+const MAX_TURN_SPEED: f32 = 0.08;
+// Reversing is slower than driving forwards
+const REVERSE_MULTIPLIER: f32 = 0.6;
+// The fraction of its sideways speed a tank loses each tick, as its treads grip the ground
+const SIDEWAYS_GRIP: f32 = 0.6;
+// How far off (in radians) the AI has to be facing before it steers at full lock
+const AI_FULL_STEER_ANGLE: f32 = 0.3;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ControlScheme {
+    // Move in the direction pressed
+    #[default]
+    Direct,
+    // Turn the hull and drive along it
+    Tank,
+}
+
+#[derive(Component)]
+pub struct TankDrive {
+    // The way the hull is facing, in radians
+    pub heading: f32,
+    // Radians per tick
+    pub turn_speed: f32,
+}
+
+impl TankDrive {
+    pub fn new(heading: f32) -> TankDrive {
+        TankDrive {
+            heading,
+            turn_speed: 0.0,
+        }
+    }
+
+    pub fn forward(&self) -> Vec2 {
+        Vec2::from_angle(self.heading)
+    }
+
+    // Drives for one tick. `throttle` is from -1.0 (full reverse) to 1.0 (full ahead), and `steer`
+    // from -1.0 (clockwise) to 1.0 (anticlockwise). `acceleration` is how much speed full throttle
+    // adds per tick. Forward friction is left to the caller, as it is the same for both schemes.
+    pub fn drive(&mut self, velocity: &mut Vec2, throttle: f32, steer: f32, acceleration: f32) {
+        self.turn_speed = ((self.turn_speed + steer.clamp(-1.0, 1.0) * TURN_ACCELERATION)
+            * TURN_FRICTION)
+            .clamp(-MAX_TURN_SPEED, MAX_TURN_SPEED);
+        self.heading = angle_between(0.0, self.heading + self.turn_speed);
+
+        let throttle = throttle.clamp(-1.0, 1.0);
+        let throttle = if throttle < 0.0 {
+            throttle * REVERSE_MULTIPLIER
+        } else {
+            throttle
+        };
+        let forward = self.forward();
+        *velocity += forward * throttle * acceleration;
+
+        let sideways = *velocity - forward * velocity.dot(forward);
+        *velocity -= sideways * SIDEWAYS_GRIP;
+    }
+
+    // Steers towards `wanted` (a direction, or zero to stop) and drives forwards once roughly
+    // facing it. This is how the AI drives.
+    pub fn drive_towards(&mut self, velocity: &mut Vec2, wanted: Vec2, acceleration: f32) {
+        let Some(wanted) = wanted.try_normalize() else {
+            self.drive(velocity, 0.0, 0.0, acceleration);
+            return;
+        };
+        let steer = angle_between(self.heading, wanted.to_angle()) / AI_FULL_STEER_ANGLE;
+        let throttle = self.forward().dot(wanted).max(0.0);
+        self.drive(velocity, throttle, steer, acceleration);
+    }
+}
@@ -0,0 +1,121 @@
+use bevy::prelude::*;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+use bevy::utils::HashSet;
+
+use crate::camera::{visible_area, FollowCamera};
+use crate::tanks::{Ai, TANK_SIZE};
+
+// Arrows around the edge of the screen pointing at enemies that are off it. The
+// further away the enemy is, the fainter its arrow.
+
+const ARROW_LENGTH: f32 = 16.0;
+const ARROW_WIDTH: f32 = 12.0;
+// How far in from the edge of the screen the arrows sit
+const ARROW_MARGIN: f32 = 20.0;
+const ARROW_COLOUR: Color = Color::srgb(0.89, 0.56, 0.26);
+// Arrows fade from fully opaque next to the screen down to ARROW_MIN_ALPHA this
+// far beyond it
+const ARROW_FADE_DISTANCE: f32 = 800.0;
+const ARROW_MIN_ALPHA: f32 = 0.25;
+// Above the world, but below the crosshair
+const ARROW_Z: f32 = 40.0;
+
+#[derive(Component)]
+pub struct EnemyArrow {
+    target: Entity,
+}
+
+// Every arrow is the same shape, so they all share one mesh
+#[derive(Resource)]
+pub struct ArrowMesh(Mesh2dHandle);
+
+pub fn create_arrow_mesh(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    // Pointing right, which is rotated to point at the enemy
+    let arrow = Triangle2d::new(
+        Vec2::new(ARROW_LENGTH / 2.0, 0.0),
+        Vec2::new(-ARROW_LENGTH / 2.0, ARROW_WIDTH / 2.0),
+        Vec2::new(-ARROW_LENGTH / 2.0, -ARROW_WIDTH / 2.0),
+    );
+    commands.insert_resource(ArrowMesh(Mesh2dHandle(meshes.add(arrow))));
+}
+
+pub fn update_enemy_arrows(
+    mut commands: Commands,
+    arrow_mesh: Res<ArrowMesh>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    enemies: Query<(Entity, &Transform), (With<Ai>, Without<EnemyArrow>)>,
+    mut arrows: Query<
+        (
+            Entity,
+            &EnemyArrow,
+            &mut Transform,
+            &mut Visibility,
+            &Handle<ColorMaterial>,
+        ),
+        Without<FollowCamera>,
+    >,
+    cameras: Query<(&Transform, &OrthographicProjection), With<FollowCamera>>,
+) {
+    let Ok((camera, projection)) = cameras.get_single() else {
+        return;
+    };
+    let view = visible_area(camera, projection);
+    // The arrows go around this smaller rectangle
+    let half_size =
+        (view.half_size() - Vec2::splat(ARROW_MARGIN)).max(Vec2::ONE);
+
+    let mut has_arrow = HashSet::new();
+    for (arrow_entity, arrow, mut transform, mut visibility, material) in
+        arrows.iter_mut()
+    {
+        let Ok((_, target)) = enemies.get(arrow.target) else {
+            commands.entity(arrow_entity).despawn_recursive();
+            materials.remove(material);
+            continue;
+        };
+        has_arrow.insert(arrow.target);
+
+        let target = target.translation.truncate();
+        // Enemies that can be seen (even partly) don't need an arrow
+        if view.inflate(TANK_SIZE).contains(target) {
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+        *visibility = Visibility::Visible;
+
+        // Put the arrow where the line from the middle of the screen to the
+        // enemy crosses the arrows' rectangle
+        let direction = target - view.center();
+        let scale = (half_size / direction.abs()).min_element();
+        transform.translation =
+            (view.center() + direction * scale).extend(ARROW_Z);
+        transform.rotation = Quat::from_rotation_z(direction.to_angle());
+
+        let distance = target.distance(target.clamp(view.min, view.max));
+        let fade = (distance / ARROW_FADE_DISTANCE).min(1.0);
+        if let Some(material) = materials.get_mut(material) {
+            material.color =
+                ARROW_COLOUR.with_alpha(1.0 - fade * (1.0 - ARROW_MIN_ALPHA));
+        }
+    }
+
+    for (entity, _) in enemies.iter() {
+        if has_arrow.contains(&entity) {
+            continue;
+        }
+        commands.spawn((
+            MaterialMesh2dBundle {
+                mesh: arrow_mesh.0.clone(),
+                // Each arrow fades on its own, so it needs its own material
+                material: materials.add(ARROW_COLOUR),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            EnemyArrow { target: entity },
+            Name::new("Enemy arrow"),
+        ));
+    }
+}
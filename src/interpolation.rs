@@ -0,0 +1,63 @@
+use bevy::prelude::*;
+
+// The game is simulated in fixed ticks (see TIME_STEP), which don't line up with the frames it is
+// drawn on. Anything the simulation moves gets an Interpolated component, and is drawn part way
+// between where it was on the last two ticks, so it moves smoothly at any refresh rate.
+//
+// Between ticks, the Transform holds where the thing is drawn. At the start of each tick it is put
+// back to where the simulation left it, and at the end of the tick the new position is recorded.
+
+#[derive(Clone, Copy)]
+struct Pose {
+    translation: Vec3,
+    rotation: Quat,
+}
+
+impl Pose {
+    fn of(transform: &Transform) -> Pose {
+        Pose {
+            translation: transform.translation,
+            rotation: transform.rotation,
+        }
+    }
+}
+
+#[derive(Component, Default)]
+pub struct Interpolated {
+    previous: Option<Pose>,
+    current: Option<Pose>,
+}
+
+pub fn restore_simulated_transforms(mut things: Query<(&mut Transform, &mut Interpolated)>) {
+    for (mut transform, mut interpolated) in things.iter_mut() {
+        match interpolated.current {
+            Some(current) => {
+                transform.translation = current.translation;
+                transform.rotation = current.rotation;
+            }
+            // Spawned since the last tick, so it is still where it was spawned
+            None => interpolated.current = Some(Pose::of(&transform)),
+        }
+    }
+}
+
+pub fn record_simulated_transforms(mut things: Query<(&Transform, &mut Interpolated)>) {
+    for (transform, mut interpolated) in things.iter_mut() {
+        interpolated.previous = interpolated.current.or(Some(Pose::of(transform)));
+        interpolated.current = Some(Pose::of(transform));
+    }
+}
+
+// Runs before anything in Update that looks at where things are, such as the camera
+pub fn interpolate_transforms(
+    fixed_time: Res<Time<Fixed>>,
+    mut things: Query<(&mut Transform, &Interpolated)>,
+) {
+    let blend = fixed_time.overstep_fraction();
+    for (mut transform, interpolated) in things.iter_mut() {
+        if let (Some(previous), Some(current)) = (interpolated.previous, interpolated.current) {
+            transform.translation = previous.translation.lerp(current.translation, blend);
+            transform.rotation = previous.rotation.slerp(current.rotation, blend);
+        }
+    }
+}
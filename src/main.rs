@@ -13,17 +13,25 @@ use bevy::{
     prelude::*,
     render::camera::ScalingMode,
     sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+    utils::{HashMap, HashSet},
     window::*,
 };
 
 use bevy_embedded_assets::EmbeddedAssetPlugin;
 use dot32_intro::*;
+use rand::seq::SliceRandom;
 use rand::Rng;
 use std::env; // Detect OS for OS specific keybinds
               // use bevy_inspector_egui::{WorldInspectorPlugin, RegisterInspectable, WorldInspectorParams};
               // use bevy_inspector_egui::quick::WorldInspectorPlugin;
 mod utils;
-use crate::utils::Health;
+use crate::utils::{
+    sweep_circle_circle, sweep_circle_rect, update_invulnerability, Died,
+    Health, Invulnerable, Shield, SpatialGrid,
+};
+
+mod tank_classes;
+use tank_classes::*;
 
 mod tanks;
 use tanks::*;
@@ -31,16 +39,111 @@ use tanks::*;
 mod sound;
 use sound::*;
 
+mod arena;
+use arena::*;
+
+mod camera;
+use camera::*;
+
+mod camera_effects;
+use camera_effects::*;
+
+mod crosshair;
+use crosshair::*;
+
+mod display;
+use display::*;
+
+mod driving;
+use driving::*;
+
+mod enemy_arrows;
+use enemy_arrows::*;
+
+mod progress_bars;
+use progress_bars::*;
+
+mod minimap;
+use minimap::*;
+
 mod healthbars;
 use healthbars::*;
-pub const MAX_HEALTH: u8 = 5;
+
+mod settings;
+use settings::*;
+
+mod music;
+use music::*;
+
+mod synth;
+use synth::*;
+
+mod particles;
+use particles::*;
+
+mod treads;
+use treads::*;
+
+mod pickups;
+use pickups::*;
+
+mod reload;
+use reload::*;
+
+mod spatial;
+use spatial::*;
+
+mod interpolation;
+use interpolation::*;
+
+mod countdown;
+use countdown::*;
 
 const TIME_STEP: f64 = 1.0 / 60.0; // FPS
 const MUTE: bool = false;
 
-const BULLET_SIZE: f32 = 6.0;
-const KNOCKBACK: f32 = 5.0;
-
+// Mass per square pixel of a bullet's cross-section, which sets how hard
+// bullets push tanks back
+const BULLET_DENSITY: f32 = 1.0 / 72.0;
+// How bouncy tanks are when they hit each other. 0 is a dead stop, 1 is a
+// perfect bounce.
+const TANK_RESTITUTION: f32 = 0.4;
+// Tanks on opposing teams closing on each other faster than this (pixels per
+// tick) hurt each other
+const RAM_SPEED: f32 = 6.0;
+// Damage per pixel per tick of closing speed above RAM_SPEED. The lighter tank
+// takes more of it.
+const RAM_DAMAGE: f32 = 0.25;
+// The most ticks AI keep wandering in one direction for
+const AI_MAX_STEPS: u32 = 60;
+// How far away AI can spot players from
+const AI_SIGHT_RANGE: f32 = 900.0;
+// How far past the edge of a tank's hull the end of its barrel is
+const MUZZLE_REACH: f32 = 14.0;
+
+// How many AI there are at the start of the game
+const STARTING_ENEMIES: usize = 2;
+// How long a freshly spawned player can't be damaged for, in seconds
+const SPAWN_INVULNERABILITY: f32 = 2.0;
+// The player slowly heals once they haven't been hit for a while
+const REGENERATION_DELAY: f32 = 5.0;
+const REGENERATION_RATE: f32 = 0.25; // Health per second
+
+// The player's shield, which takes damage before their health does
+const SHIELD_STRENGTH: f32 = 2.0;
+const SHIELD_RECHARGE_DELAY: f32 = 3.0;
+const SHIELD_RECHARGE_RATE: f32 = 0.5; // Shield per second
+const SHIELD_FLASH_COLOUR: Color = Color::srgb(0.55, 0.85, 1.0);
+
+// Ammunition, used when limited ammo is turned on in the settings
+const PLAYER_MAGAZINE_SIZE: u32 = 6;
+const PLAYER_RESERVE_AMMO: u32 = 30;
+const PLAYER_RELOAD_TIME: f32 = 1.5;
+const AI_MAGAZINE_SIZE: u32 = 4;
+const AI_RELOAD_TIME: f32 = 2.0;
+
+// The starting size of the window, and the size of the area the camera shows.
+// The arena itself can be bigger, and the window resized (see display.rs).
 const GAME_WIDTH: f32 = 800.0;
 const GAME_HEIGHT: f32 = 600.0;
 
@@ -63,43 +166,198 @@ fn main() {
                 .build(),
         )
         .init_state::<AppState>()
+        .init_state::<RoundState>()
         .add_plugins(EmbeddedAssetPlugin::default())
         .insert_resource(ClearColor(Color::srgb(0.49, 0.31, 0.25)))
         .insert_resource(AiKilled { score: 0 })
-        .add_systems(Startup, (create_player, create_enemy, setup))
-        // Game systems
+        .add_event::<Died>()
+        .init_asset::<TankClasses>()
+        .init_asset_loader::<TankClassesLoader>()
+        .init_asset::<SynthPresets>()
+        .init_asset_loader::<SynthPresetsLoader>()
+        .insert_resource(Settings::default())
+        .insert_resource(Arena::default())
+        .insert_resource(TankGrid::default())
+        .insert_resource(CursorWorldPosition::default())
+        .insert_resource(CameraEffects::default())
+        .insert_resource(MusicIntensity::default())
+        .insert_resource(RepairKitTimer::default())
+        .insert_resource(PowerUpSpawner::default())
+        .insert_resource(AmmoCrateTimer::default())
+        .insert_resource(Countdown::default())
+        .insert_resource(ReloadPressed::default())
         .add_systems(
-            Update,
+            Startup,
+            (
+                create_synth_sounds,
+                load_recorded_sounds,
+                load_tank_classes,
+                setup,
+                start_music,
+                spawn_cursor_reload_indicator,
+                spawn_crosshair,
+                spawn_letterbox_camera,
+                spawn_ammo_text,
+                spawn_minimap,
+                create_arrow_mesh,
+                create_scorch_mesh,
+                spawn_countdown_text,
+            ),
+        )
+        // The simulation. Everything that changes the state of the game happens
+        // in fixed ticks, in the order of SimulationSet, so it plays out the
+        // same at any frame rate.
+        .configure_sets(
+            FixedUpdate,
             (
-                mouse_button_input,
-                ai_rotate,
+                SimulationSet::Input,
+                SimulationSet::Movement,
+                SimulationSet::Collision,
+                SimulationSet::Damage,
+                SimulationSet::Upkeep,
+            )
+                .chain()
+                .run_if(in_state(AppState::Game)),
+        )
+        // Nobody can drive, aim or fire until the countdown at the start of the
+        // round is over
+        .configure_sets(
+            FixedUpdate,
+            SimulationSet::Input.run_if(in_state(RoundState::Fighting)),
+        )
+        .add_systems(
+            FixedUpdate,
+            (mouse_button_input, reload_input, ai_rotate, ai_reload)
+                .in_set(SimulationSet::Input),
+        )
+        .add_systems(
+            FixedUpdate,
+            (
+                (movement, ai_movement).run_if(in_state(RoundState::Fighting)),
                 keep_tanks_on_screen,
-                keep_healthbars_on_screen,
-                kill_bullets,
-                hurt_tanks,
-                collide_tanks,
+                rebuild_tank_grid,
+            )
+                .chain()
+                .in_set(SimulationSet::Movement),
+        )
+        .add_systems(
+            FixedUpdate,
+            // Bullets are moved after tanks have been pushed apart, so they are
+            // checked against where the tanks end up
+            (collide_tanks, collide_bullets, update_bullets)
+                .chain()
+                .in_set(SimulationSet::Collision),
+        )
+        .add_systems(
+            FixedUpdate,
+            (hurt_tanks, handle_deaths)
+                .chain()
+                .in_set(SimulationSet::Damage),
+        )
+        .add_systems(
+            FixedUpdate,
+            (
+                update_invulnerability,
+                update_hit_timer,
+                regenerate_health,
+                recharge_shields,
+                update_magazines,
+                (spawn_repair_kits, collect_repair_kits),
+                (spawn_ammo_crates, collect_ammo_crates),
+                (spawn_power_ups, collect_power_ups, update_effects),
+            )
+                .in_set(SimulationSet::Upkeep),
+        )
+        // Moving things are drawn between their last two positions (see
+        // interpolation.rs)
+        .add_systems(FixedFirst, restore_simulated_transforms)
+        .add_systems(FixedLast, record_simulated_transforms)
+        // Game systems. These show what the simulation is doing, and take input
+        // that has to be caught on the frame it happens.
+        .add_systems(
+            Update,
+            (
+                (
+                    follow_player,
+                    apply_camera_effects,
+                    (keep_bars_on_screen, update_minimap, update_enemy_arrows),
+                )
+                    .chain(),
                 update_kills_text,
-                update_healthbar,
-                update_healthbar_border,
-                update_healthbar_sides,
+                (
+                    read_bar_source::<Health>,
+                    read_bar_source::<Shield>,
+                    update_reload_indicators,
+                    update_progress_bars,
+                    update_bar_sides,
+                )
+                    .chain(),
                 pause_system,
-                update_hit_timer,
-                flash_yellow,
+                // Presses during the countdown are ignored, like all other
+                // input
+                buffer_reload_input.run_if(in_state(RoundState::Fighting)),
+                update_ammo_text,
+                update_effect_icons,
+                (
+                    flash_yellow,
+                    update_particles,
+                    update_scorch_marks,
+                    rotate_hulls,
+                    leave_tread_marks,
+                    update_tread_marks,
+                ),
                 button_system,
             )
+                .after(interpolate_transforms)
                 .run_if(in_state(AppState::Game)),
         )
+        .add_systems(Update, interpolate_transforms)
+        // The countdown at the start of each round
+        .add_systems(OnEnter(RoundState::Countdown), start_countdown)
+        .add_systems(
+            Update,
+            (
+                run_countdown
+                    .run_if(in_state(AppState::Game).and_then(intro_finished)),
+                update_countdown_text,
+            )
+                .chain(),
+        )
         // Pause systems
         .add_systems(
             Update,
-            (update_healthbar_sides, unpause_system)
+            (update_bar_sides, unpause_system)
                 .run_if(in_state(AppState::Paused)),
         )
-        // Fixed-update game systems
+        // Music systems run regardless of state, as the music keeps playing
+        // (quieter) while paused
         .add_systems(
-            FixedUpdate,
-            (update_bullets, movement, ai_movement)
-                .run_if(in_state(AppState::Game)),
+            Update,
+            (
+                spawn_music_layers.run_if(resource_exists::<LoadingMusic>),
+                update_music_intensity,
+                crossfade_music,
+            )
+                .chain(),
+        )
+        .add_systems(
+            Update,
+            (toggle_fullscreen, fit_viewport_to_window, update_hit_stop),
+        )
+        .add_systems(
+            Update,
+            (fall_back_to_built_in_classes, spawn_starting_tanks).chain(),
+        )
+        .add_systems(Update, (apply_synth_presets, resolve_recorded_sounds))
+        .add_systems(
+            Update,
+            apply_ammo_setting.run_if(resource_changed::<Settings>),
+        )
+        .add_systems(
+            Update,
+            (update_cursor_world_position, update_crosshair)
+                .chain()
+                .after(follow_player),
         )
         .insert_resource(Time::<Fixed>::from_seconds(TIME_STEP))
         // .add_plugins(WorldInspectorPlugin::new())
@@ -107,24 +365,36 @@ fn main() {
         .run();
 }
 
-fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    arena: Res<Arena>,
+    settings: Res<Settings>,
+) {
     // commands.spawn(Camera2dBundle::default());
     commands.spawn((
         Camera2dBundle {
             transform: Transform::from_xyz(0.0, 0.0, 100.0),
             tonemapping: bevy::core_pipeline::tonemapping::Tonemapping::None,
-            // camera: Camera {
-            //     ..default()
-            // },
+            // The letterbox camera has already cleared the window, and the
+            // background covers everything this camera can see
+            camera: Camera {
+                clear_color: ClearColorConfig::None,
+                ..default()
+            },
             projection: OrthographicProjection {
-                scaling_mode: ScalingMode::AutoMin {
-                    min_width: GAME_WIDTH,
-                    min_height: GAME_HEIGHT,
+                // The viewport is always this shape (see display.rs), so this
+                // only scales the view to fit it
+                scaling_mode: ScalingMode::Fixed {
+                    width: GAME_WIDTH,
+                    height: GAME_HEIGHT,
                 },
                 ..default()
             },
             ..default()
         },
+        FollowCamera,
+        IsDefaultUiCamera,
         // bevy::render::view::ColorGrading {
         //     exposure: 0.0,
         //     gamma: 1.0,
@@ -139,7 +409,7 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
         .spawn(SpriteBundle {
             sprite: Sprite {
                 color: Color::srgb(0.7, 0.55, 0.41),
-                custom_size: Some(Vec2::new(GAME_WIDTH, GAME_HEIGHT)),
+                custom_size: Some(arena.size),
                 ..default()
             },
             transform: Transform::from_translation(Vec3::new(0.0, 0.0, -30.0)),
@@ -222,11 +492,139 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
                         },
                     ));
                 });
+            parent
+                .spawn((
+                    ButtonBundle {
+                        style: Style {
+                            width: Val::Px(120.0),
+                            height: Val::Px(40.0),
+                            border: UiRect::all(Val::Px(1.0)),
+                            // horizontally center child text
+                            justify_content: JustifyContent::Center,
+                            // vertically center child text
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        border_color: BorderColor(Color::WHITE),
+                        border_radius: BorderRadius::MAX,
+                        background_color: Color::srgb(0., 0., 0.).into(),
+                        ..default()
+                    },
+                    Name::new("Difficulty button"),
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        difficulty_label(settings.difficulty),
+                        TextStyle {
+                            font: asset_server
+                                .load("fonts/PT_Sans/PTSans-Regular.ttf"),
+                            font_size: 20.0,
+                            color: Color::srgb(0.9, 0.9, 0.9),
+                        },
+                    ));
+                });
+            parent
+                .spawn((
+                    ButtonBundle {
+                        style: Style {
+                            width: Val::Px(150.0),
+                            height: Val::Px(40.0),
+                            border: UiRect::all(Val::Px(1.0)),
+                            // horizontally center child text
+                            justify_content: JustifyContent::Center,
+                            // vertically center child text
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        border_color: BorderColor(Color::WHITE),
+                        border_radius: BorderRadius::MAX,
+                        background_color: Color::srgb(0., 0., 0.).into(),
+                        ..default()
+                    },
+                    Name::new("Ammo button"),
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        ammo_label(settings.limited_ammo),
+                        TextStyle {
+                            font: asset_server
+                                .load("fonts/PT_Sans/PTSans-Regular.ttf"),
+                            font_size: 20.0,
+                            color: Color::srgb(0.9, 0.9, 0.9),
+                        },
+                    ));
+                });
+            parent
+                .spawn((
+                    ButtonBundle {
+                        style: Style {
+                            width: Val::Px(170.0),
+                            height: Val::Px(40.0),
+                            border: UiRect::all(Val::Px(1.0)),
+                            // horizontally center child text
+                            justify_content: JustifyContent::Center,
+                            // vertically center child text
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        border_color: BorderColor(Color::WHITE),
+                        border_radius: BorderRadius::MAX,
+                        background_color: Color::srgb(0., 0., 0.).into(),
+                        ..default()
+                    },
+                    Name::new("Bullet clash button"),
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        bullet_clash_label(settings.bullet_collisions),
+                        TextStyle {
+                            font: asset_server
+                                .load("fonts/PT_Sans/PTSans-Regular.ttf"),
+                            font_size: 20.0,
+                            color: Color::srgb(0.9, 0.9, 0.9),
+                        },
+                    ));
+                });
         });
+}
 
-    commands.add(|world: &mut World| {
-        world.run_system_once(create_enemy);
-    })
+// The difficulty button shows the current difficulty, and is clicked to change
+// it
+fn difficulty_label(difficulty: Difficulty) -> String {
+    format!("AI: {}", difficulty.name())
+}
+
+// The ammo button turns limited ammo on and off
+fn ammo_label(limited_ammo: bool) -> String {
+    match limited_ammo {
+        true => "Ammo: Limited".to_string(),
+        false => "Ammo: Unlimited".to_string(),
+    }
+}
+
+// The bullet clash button turns bullet collisions on and off. AI only shoot
+// down bullets on the harder difficulties while it is on.
+fn bullet_clash_label(bullet_collisions: bool) -> String {
+    match bullet_collisions {
+        true => "Bullet clash: On".to_string(),
+        false => "Bullet clash: Off".to_string(),
+    }
+}
+
+// The steps of each fixed tick of the simulation, in the order they run
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+enum SimulationSet {
+    // The player's controls and the AI's decisions, including aiming and firing
+    Input,
+    // Tanks driving, kept inside the arena
+    Movement,
+    // Tanks pushing each other apart, and bullets flying and hitting things
+    Collision,
+    // Hits turned into damage, and destroyed tanks removed
+    Damage,
+    // Everything else that plays out over time, like regeneration, reloading,
+    // pickups and power-ups
+    Upkeep,
 }
 
 #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
@@ -236,14 +634,51 @@ enum AppState {
     Game,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
 enum TurretOf {
     Player,
     Ai,
 }
 
+impl TurretOf {
+    // Player bullets can only hit AI, and AI bullets can only hit players
+    fn can_hit(self, is_ai: bool) -> bool {
+        match self {
+            TurretOf::Player => is_ai,
+            TurretOf::Ai => !is_ai,
+        }
+    }
+}
+
 #[derive(Component)]
 struct Bullet {
     from: TurretOf,
+    damage: f32,
+    // Pixels per tick
+    speed: f32,
+    // The bullet's radius
+    size: f32,
+    // How many more times the bullet can bounce off a wall before it is
+    // destroyed
+    bounces: u8,
+}
+
+impl Bullet {
+    fn mass(&self) -> f32 {
+        self.size * self.size * BULLET_DENSITY
+    }
+
+    fn momentum(&self) -> f32 {
+        self.mass() * self.speed
+    }
+}
+
+// Put on a bullet when it hits a tank, and handled by hurt_tanks
+#[derive(Component)]
+struct BulletHit {
+    tank: Entity,
+    // Points from the tank towards the bullet
+    normal: Vec2,
 }
 
 #[derive(Component)]
@@ -259,188 +694,253 @@ struct AiKilled {
     score: u8,
 }
 
+// Spawns a tank of the given class, set up for its team
+fn spawn_tank(
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    settings: &Settings,
+    class: &TankClass,
+    team: Team,
+    position: Vec2,
+) -> Entity {
+    let tank = commands
+        .spawn(TankBundle::new(class, position))
+        .with_children(|parent| {
+            spawn_tank_parts(parent, meshes, materials, class, team);
+            match team {
+                Team::Player => {
+                    spawn_shieldbar(parent, meshes, materials);
+                    spawn_healthbar(parent, meshes, materials, true);
+                    spawn_reload_bar(parent, meshes, materials, class.radius);
+                }
+                Team::Ai => {
+                    spawn_healthbar(parent, meshes, materials, false);
+                }
+            }
+        })
+        .id();
+
+    let controls = match team {
+        Team::Player => {
+            commands.entity(tank).insert((
+                Player,
+                Invulnerable(SPAWN_INVULNERABILITY),
+                Regeneration {
+                    delay: REGENERATION_DELAY,
+                    rate: REGENERATION_RATE,
+                },
+                Shield::new(
+                    SHIELD_STRENGTH,
+                    SHIELD_RECHARGE_DELAY,
+                    SHIELD_RECHARGE_RATE,
+                ),
+                Name::new("Player"),
+            ));
+            settings.player_controls
+        }
+        Team::Ai => {
+            commands
+                .entity(tank)
+                .insert((AiBundle::new(), Name::new("Enemy")));
+            settings.ai_controls
+        }
+    };
+    if settings.limited_ammo {
+        commands.entity(tank).insert(full_magazine(team));
+    }
+    if controls == ControlScheme::Tank {
+        commands.entity(tank).insert(TankDrive::new(0.0));
+    }
+    tank
+}
+
+fn full_magazine(team: Team) -> Magazine {
+    match team {
+        Team::Player => Magazine::new(
+            PLAYER_MAGAZINE_SIZE,
+            Some(PLAYER_RESERVE_AMMO),
+            PLAYER_RELOAD_TIME,
+        ),
+        // AI never run out of spare ammo, but still have to reload
+        Team::Ai => Magazine::new(AI_MAGAZINE_SIZE, None, AI_RELOAD_TIME),
+    }
+}
+
+// Gives every tank a magazine, or takes them all away, when limited ammo is
+// turned on or off
+fn apply_ammo_setting(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    tanks: Query<(Entity, Has<Player>, Has<Magazine>), With<Tank>>,
+) {
+    for (tank, is_player, has_magazine) in tanks.iter() {
+        if settings.limited_ammo && !has_magazine {
+            let team = if is_player { Team::Player } else { Team::Ai };
+            commands.entity(tank).insert(full_magazine(team));
+        } else if !settings.limited_ammo && has_magazine {
+            commands.entity(tank).remove::<Magazine>();
+        }
+    }
+}
+
 fn create_player(
     mut commands: Commands,
+    settings: Res<Settings>,
+    classes: TankClassLibrary,
 
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
-    commands
-        .spawn(TankBundle::new(&mut meshes, &mut materials, 4)) // "4" is the amount of health we spawn the tank with
-        .insert(Player)
-        .insert(Name::new("Player"))
-        .with_children(|parent| {
-            parent
-                .spawn((
-                    MaterialMesh2dBundle {
-                        mesh: Mesh2dHandle(meshes.add(Circle { radius: 16.0 })),
-                        material: materials.add(Color::srgb(0.35, 0.6, 0.99)),
-                        transform: Transform::from_xyz(0.0, 0.0, 0.1),
-                        ..Default::default()
-                    },
-                    OriginalColour(Color::srgb(0.35, 0.6, 0.99)),
-                ))
-                .with_children(|parent| {
-                    parent.spawn(BearingBundle::new()).with_children(
-                        |parent| {
-                            parent.spawn(TurretBundle::new());
-                        },
-                    );
-                });
-            parent
-                .spawn(HealthbarBundle::new(4)) // "4" is the max health
-                .with_children(|parent| {
-                    parent.spawn(HealthbarSideBundle::new(
-                        &mut meshes,
-                        &mut materials,
-                        Side::Left,
-                    ));
-                    parent.spawn(HealthbarSideBundle::new(
-                        &mut meshes,
-                        &mut materials,
-                        Side::Right,
-                    ));
-                });
-            parent.spawn(HealthbarBorderBundle::new()).with_children(
-                |parent| {
-                    parent.spawn(HealthbarSideBundle::new(
-                        &mut meshes,
-                        &mut materials,
-                        Side::Left,
-                    ));
-                    parent.spawn(HealthbarSideBundle::new(
-                        &mut meshes,
-                        &mut materials,
-                        Side::Right,
-                    ));
-                },
-            );
-        });
+    let Some(class) = classes.get(&settings.player_class) else {
+        warn!("There is no tank class called {:?}", settings.player_class);
+        return;
+    };
+    spawn_tank(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        &settings,
+        class,
+        Team::Player,
+        Vec2::ZERO,
+    );
 }
 
 fn create_enemy(
     mut commands: Commands,
+    settings: Res<Settings>,
+    classes: TankClassLibrary,
 
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
-    commands
-        .spawn(TankBundle::new(&mut meshes, &mut materials, 4)) // "4" is the amount of health we spawn the tank with
-        .insert(AiBundle::new())
-        .insert(Name::new("Enemy"))
-        .with_children(|parent| {
-            parent
-                .spawn((
-                    MaterialMesh2dBundle {
-                        mesh: Mesh2dHandle(meshes.add(Circle { radius: 16.0 })),
-                        material: materials.add(Color::srgb(0.89, 0.56, 0.26)),
-                        transform: Transform::from_xyz(0.0, 0.0, 0.1),
-                        ..Default::default()
-                    },
-                    OriginalColour(Color::srgb(0.89, 0.56, 0.26)),
-                ))
-                .with_children(|parent| {
-                    parent.spawn(BearingBundle::new()).with_children(
-                        |parent| {
-                            parent.spawn(TurretBundle::new());
-                        },
-                    );
-                });
-            parent
-                .spawn(HealthbarBundle::new(4)) // "4" is the max health
-                .with_children(|parent| {
-                    parent.spawn(HealthbarSideBundle::new(
-                        &mut meshes,
-                        &mut materials,
-                        Side::Left,
-                    ));
-                    parent.spawn(HealthbarSideBundle::new(
-                        &mut meshes,
-                        &mut materials,
-                        Side::Right,
-                    ));
-                });
-            parent.spawn(HealthbarBorderBundle::new()).with_children(
-                |parent| {
-                    parent.spawn(HealthbarSideBundle::new(
-                        &mut meshes,
-                        &mut materials,
-                        Side::Left,
-                    ));
-                    parent.spawn(HealthbarSideBundle::new(
-                        &mut meshes,
-                        &mut materials,
-                        Side::Right,
-                    ));
-                },
-            );
-        });
+    // Each AI is a random one of the classes they are allowed to be
+    let Some(name) = settings.ai_classes.choose(&mut rand::thread_rng()) else {
+        return;
+    };
+    let Some(class) = classes.get(name) else {
+        warn!("There is no tank class called {:?}", name);
+        return;
+    };
+    spawn_tank(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        &settings,
+        class,
+        Team::Ai,
+        Vec2::ZERO,
+    );
+}
+
+// The first player and AI are spawned as soon as the tank classes have loaded,
+// as they can't be spawned without them
+fn spawn_starting_tanks(
+    mut commands: Commands,
+    classes: TankClassLibrary,
+    mut spawned: Local<bool>,
+) {
+    if *spawned || !classes.is_loaded() {
+        return;
+    }
+    *spawned = true;
+    commands.add(|world: &mut World| {
+        world.run_system_once(create_player);
+        for _ in 0..STARTING_ENEMIES {
+            world.run_system_once(create_enemy);
+        }
+    });
 }
 
 fn movement(
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut positions: Query<(&mut Transform, &mut Velocity), With<Player>>,
-    time: Res<Time>,
+    mut positions: Query<
+        (
+            &mut Transform,
+            &mut Velocity,
+            &TankStats,
+            &ActiveEffects,
+            Option<&mut TankDrive>,
+        ),
+        With<Player>,
+    >,
 ) {
-    for (mut transform, mut velocity) in positions.iter_mut() {
-        if (keyboard_input.pressed(KeyCode::ArrowLeft)
-            || keyboard_input.pressed(KeyCode::KeyA))
-            && LENGTH + FADE + 1.0 < time.elapsed_seconds() as f32
+    for (mut transform, mut velocity, stats, effects, drive) in
+        positions.iter_mut()
+    {
+        let speed = stats.acceleration * effects.speed_multiplier();
+        let mut input = Vec2::ZERO;
+        if keyboard_input.pressed(KeyCode::ArrowLeft)
+            || keyboard_input.pressed(KeyCode::KeyA)
         {
-            velocity.value.x -= TANK_SPEED;
+            input.x -= 1.0;
         }
-        if (keyboard_input.pressed(KeyCode::ArrowRight)
-            || keyboard_input.pressed(KeyCode::KeyD))
-            && LENGTH + FADE + 1.0 < time.elapsed_seconds() as f32
+        if keyboard_input.pressed(KeyCode::ArrowRight)
+            || keyboard_input.pressed(KeyCode::KeyD)
         {
-            velocity.value.x += TANK_SPEED;
+            input.x += 1.0;
         }
-        if (keyboard_input.pressed(KeyCode::ArrowDown)
-            || keyboard_input.pressed(KeyCode::KeyS))
-            && LENGTH + FADE + 1.0 < time.elapsed_seconds() as f32
+        if keyboard_input.pressed(KeyCode::ArrowDown)
+            || keyboard_input.pressed(KeyCode::KeyS)
         {
-            velocity.value.y -= TANK_SPEED;
+            input.y -= 1.0;
         }
-        if (keyboard_input.pressed(KeyCode::ArrowUp)
-            || keyboard_input.pressed(KeyCode::KeyW))
-            && LENGTH + FADE + 1.0 < time.elapsed_seconds() as f32
+        if keyboard_input.pressed(KeyCode::ArrowUp)
+            || keyboard_input.pressed(KeyCode::KeyW)
         {
-            velocity.value.y += TANK_SPEED;
+            input.y += 1.0;
+        }
+
+        match drive {
+            // Left and right turn the hull, and up and down drive along it
+            Some(mut drive) => {
+                drive.drive(&mut velocity.value, input.y, -input.x, speed)
+            }
+            None => velocity.value += input * speed,
         }
 
-        velocity.value *= 0.9;
+        velocity.value = (velocity.value * stats.friction)
+            .clamp_length_max(stats.max_speed * effects.speed_multiplier());
 
         transform.translation += velocity.value.extend(0.0);
     }
 }
 
 fn keep_tanks_on_screen(
+    arena: Res<Arena>,
     mut tanks: Query<
-        (&mut Transform, &mut Velocity, Option<&mut DirectionAi>),
+        (
+            &mut Transform,
+            &mut Velocity,
+            &TankStats,
+            Option<&mut DirectionAi>,
+        ),
         With<Tank>,
     >,
 ) {
-    for (mut tank, mut velocity, direction) in tanks.iter_mut() {
+    for (mut tank, mut velocity, stats, direction) in tanks.iter_mut() {
+        let edge = arena.half_size() - Vec2::splat(stats.radius);
         let mut tempdir = 5;
 
-        if tank.translation.x + TANK_SIZE > GAME_WIDTH - GAME_WIDTH / 2.0 {
+        if tank.translation.x > edge.x {
             velocity.value.x = 0.0;
-            tank.translation.x = GAME_WIDTH / 2.0 - TANK_SIZE;
+            tank.translation.x = edge.x;
             tempdir = 0;
         }
-        if tank.translation.x - TANK_SIZE < -GAME_WIDTH / 2.0 {
+        if tank.translation.x < -edge.x {
             velocity.value.x = 0.0;
-            tank.translation.x = -GAME_WIDTH / 2.0 + TANK_SIZE;
+            tank.translation.x = -edge.x;
             tempdir = 1;
         }
-        if tank.translation.y + TANK_SIZE > GAME_HEIGHT - GAME_HEIGHT / 2.0 {
+        if tank.translation.y > edge.y {
             velocity.value.y = 0.0;
-            tank.translation.y = GAME_HEIGHT / 2.0 - TANK_SIZE;
+            tank.translation.y = edge.y;
             tempdir = 2;
         }
-        if tank.translation.y - TANK_SIZE < -GAME_HEIGHT / 2.0 {
+        if tank.translation.y < -edge.y {
             velocity.value.y = 0.0;
-            tank.translation.y = -GAME_HEIGHT / 2.0 + TANK_SIZE;
+            tank.translation.y = -edge.y;
             tempdir = 3;
         }
 
@@ -455,106 +955,256 @@ fn keep_tanks_on_screen(
     }
 }
 
-fn collide_tanks(mut tanks: Query<&mut Transform, With<Tank>>) {
-    // Create a vector that is as long as the number of tanks
-    let mut movements = vec![Vec2::new(0.0, 0.0); tanks.iter().count()];
-    // Find the movement of each tank
-    for (i, tank) in tanks.iter().enumerate() {
-        for (j, sibling) in tanks.iter().enumerate() {
-            if tank != sibling {
-                let distance = (tank.translation.truncate()
-                    - sibling.translation.truncate())
-                .length();
-                if distance < TANK_SIZE * 2.0 {
-                    // Gets the direction and how far it should move
-                    let direction = (tank.translation.truncate()
-                        - sibling.translation.truncate())
-                    .normalize();
-                    let move_len = (TANK_SIZE * 2.0) - distance;
-
-                    // Adds required movement into the vector
-                    movements[i] = direction * move_len * 0.5;
-                    movements[j] = direction * move_len * -0.5;
-                }
+// A tank as it was at the start of collide_tanks, so every contact is solved
+// from the same positions
+struct Body {
+    entity: Entity,
+    position: Vec2,
+    velocity: Vec2,
+    radius: f32,
+    mass: f32,
+    is_ai: bool,
+}
+
+fn collide_tanks(
+    mut commands: Commands,
+    mut tanks: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut Velocity,
+            &TankStats,
+            &mut Health,
+            &mut HitTimer,
+            Has<Ai>,
+            Has<Invulnerable>,
+            &ActiveEffects,
+            Option<&mut Shield>,
+        ),
+        With<Tank>,
+    >,
+    grid: Res<TankGrid>,
+    mut died: EventWriter<Died>,
+    mut camera_effects: ResMut<CameraEffects>,
+) {
+    let bodies: Vec<Body> = tanks
+        .iter()
+        .map(
+            |(entity, transform, velocity, stats, _, _, is_ai, ..)| Body {
+                entity,
+                position: transform.translation.truncate(),
+                velocity: velocity.value,
+                radius: stats.radius,
+                mass: stats.mass,
+                is_ai,
+            },
+        )
+        .collect();
+    let indices: HashMap<Entity, usize> = bodies
+        .iter()
+        .enumerate()
+        .map(|(i, body)| (body.entity, i))
+        .collect();
+
+    // Every contact adds to these, so a tank touching several others is pushed
+    // by all of them
+    let mut pushes = vec![Vec2::ZERO; bodies.len()];
+    let mut impulses = vec![Vec2::ZERO; bodies.len()];
+    let mut ram_damage = vec![0.0; bodies.len()];
+    for (i, a) in bodies.iter().enumerate() {
+        for entry in grid.nearby(a.position, a.radius) {
+            // Each pair is only solved once, by the first of the two
+            let Some(&j) = indices.get(&entry.entity) else {
+                continue;
+            };
+            if j <= i {
+                continue;
+            }
+            let b = &bodies[j];
+            let offset = a.position - b.position;
+            let distance = offset.length();
+            let overlap = a.radius + b.radius - distance;
+            if overlap <= 0.0 {
+                continue;
+            }
+            // Points from b to a. Tanks sitting exactly on top of each other
+            // are pushed apart sideways.
+            let normal = offset.try_normalize().unwrap_or(Vec2::X);
+            let total_mass = a.mass + b.mass;
+
+            // The heavier tank gets pushed less
+            pushes[i] += normal * overlap * b.mass / total_mass;
+            pushes[j] -= normal * overlap * a.mass / total_mass;
+
+            let closing_speed = (b.velocity - a.velocity).dot(normal);
+            if closing_speed <= 0.0 {
+                // Already moving apart
+                continue;
+            }
+            let impulse = (1.0 + TANK_RESTITUTION) * closing_speed
+                / (1.0 / a.mass + 1.0 / b.mass);
+            impulses[i] += normal * impulse / a.mass;
+            impulses[j] -= normal * impulse / b.mass;
+
+            if a.is_ai != b.is_ai && closing_speed > RAM_SPEED {
+                let damage = (closing_speed - RAM_SPEED) * RAM_DAMAGE * 2.0;
+                ram_damage[i] += damage * b.mass / total_mass;
+                ram_damage[j] += damage * a.mass / total_mass;
+                spawn_burst(
+                    &mut commands,
+                    &SPARKS,
+                    b.position + normal * b.radius,
+                    normal.perp(),
+                );
             }
         }
     }
 
-    // Apply the movement to the tanks
-    for (i, mut tank) in tanks.iter_mut().enumerate() {
-        tank.translation += movements[i].extend(0.0);
+    for (i, body) in bodies.iter().enumerate() {
+        let Ok((
+            entity,
+            mut transform,
+            mut velocity,
+            _,
+            mut health,
+            mut hit_timer,
+            is_ai,
+            invulnerable,
+            effects,
+            shield,
+        )) = tanks.get_mut(body.entity)
+        else {
+            continue;
+        };
+        transform.translation += pushes[i].extend(0.0);
+        velocity.value += impulses[i];
+
+        if ram_damage[i] > 0.0 && !invulnerable && !effects.has(PowerUp::Shield)
+        {
+            let (damage, shielded) = damage_tank(
+                entity,
+                ram_damage[i],
+                &mut health,
+                shield,
+                &mut hit_timer,
+                &mut died,
+            );
+            hit_feedback(
+                &mut commands,
+                &mut camera_effects,
+                is_ai,
+                damage,
+                shielded,
+            );
+        }
     }
 }
 
 fn ai_movement(
-    time: Res<Time>,
+    players: Query<&Transform, (With<Player>, Without<Ai>)>,
     mut positions: Query<
         (
             &mut Transform,
             &mut Velocity,
+            &TankStats,
             &mut Steps,
             &mut DirectionAi,
             &Active,
+            &ActiveEffects,
+            Option<&Magazine>,
+            Option<&mut TankDrive>,
         ),
         With<Ai>,
     >,
 ) {
-    for (mut transform, mut velocity, mut steps, mut direction, active) in
-        positions.iter_mut()
+    for (
+        mut transform,
+        mut velocity,
+        stats,
+        mut steps,
+        mut direction,
+        active,
+        effects,
+        magazine,
+        mut drive,
+    ) in positions.iter_mut()
     {
-        if steps.value < 0.0 {
+        let speed = stats.acceleration * effects.speed_multiplier();
+        let max_speed = stats.max_speed * effects.speed_multiplier();
+        // AI drive the same way as the player, whichever control scheme they
+        // use
+        let mut accelerate =
+            |velocity: &mut Velocity, wanted: Vec2| match drive.as_mut() {
+                Some(drive) => {
+                    drive.drive_towards(&mut velocity.value, wanted, speed)
+                }
+                None => velocity.value += wanted * speed,
+            };
+
+        // While reloading, AI back away from the player instead of wandering
+        // around
+        let reloading = magazine.is_some_and(|m| m.reloading.is_some());
+        if let (true, Ok(player)) = (reloading, players.get_single()) {
+            let away = (transform.translation - player.translation)
+                .truncate()
+                .normalize_or_zero();
+            accelerate(&mut velocity, away);
+            velocity.value =
+                (velocity.value * stats.friction).clamp_length_max(max_speed);
+            transform.translation += velocity.value.extend(0.0);
+            continue;
+        }
+
+        if steps.value == 0 {
             direction.value = rand::thread_rng().gen_range(0..=4) as u8;
-            steps.value = rand::thread_rng().gen_range(0..=110) as f32 / 110.0;
+            steps.value = rand::thread_rng().gen_range(0..=AI_MAX_STEPS);
         }
-        if direction.value == 0
-            && LENGTH + FADE + 1.0 < time.elapsed_seconds() as f32
-            && active.value == true
-        {
-            velocity.value.x -= TANK_SPEED;
+        let mut wanted = Vec2::ZERO;
+        if direction.value == 0 && active.value == true {
+            wanted.x -= 1.0;
         }
-        if direction.value == 1
-            && LENGTH + FADE + 1.0 < time.elapsed_seconds() as f32
-            && active.value == true
-        {
-            velocity.value.x += TANK_SPEED;
+        if direction.value == 1 && active.value == true {
+            wanted.x += 1.0;
         }
-        if direction.value == 2
-            && LENGTH + FADE + 1.0 < time.elapsed_seconds() as f32
-            && active.value == true
-        {
-            velocity.value.y -= TANK_SPEED;
+        if direction.value == 2 && active.value == true {
+            wanted.y -= 1.0;
         }
-        if direction.value == 3
-            && LENGTH + FADE + 1.0 < time.elapsed_seconds() as f32
-            && active.value == true
-        {
-            velocity.value.y += TANK_SPEED;
+        if direction.value == 3 && active.value == true {
+            wanted.y += 1.0;
         }
+        accelerate(&mut velocity, wanted);
 
-        velocity.value *= 0.9;
+        velocity.value =
+            (velocity.value * stats.friction).clamp_length_max(max_speed);
 
         transform.translation += velocity.value.extend(0.0);
 
-        steps.value -= time.delta_seconds();
+        steps.value = steps.value.saturating_sub(1);
     }
 }
 
 fn mouse_button_input(
     // Shoot bullets and rotate turret to point at mouse
     buttons: Res<ButtonInput<MouseButton>>,
-    primary_window: Query<&Window, With<PrimaryWindow>>,
+    cursor: Res<CursorWorldPosition>,
+    mut camera_effects: ResMut<CameraEffects>,
     time: Res<Time>,
 
     mut commands: Commands,
     // world: &mut World,
     mut positions: Query<
-        (&mut Transform, &mut AttackTimer, &Children),
+        (
+            &mut Transform,
+            &mut AttackTimer,
+            &Weapon,
+            &TankStats,
+            &TurretTraverse,
+            &Children,
+            &ActiveEffects,
+            Option<&mut Magazine>,
+        ),
         With<Player>,
     >,
-    mut tank_child_query: Query<
-        &Children,
-        (Without<Player>, Without<Turret>, Without<Bearing>),
-    >,
     mut bearings: Query<
         (&mut Transform, &Children),
         (With<Bearing>, Without<Player>, Without<Turret>),
@@ -564,119 +1214,98 @@ fn mouse_button_input(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
-    let Ok(window) = primary_window.get_single() else {
+    // The cursor's position is already in world space (see camera.rs)
+    let Some(mouse_coords) = cursor.0 else {
         return;
     };
-    if let Some(_position) = window.cursor_position() {
-        match Some(_position) {
-            Some(vec) => {
-                for (player, mut attack_timer, children) in positions.iter_mut()
-                {
-                    let window_size =
-                        Vec2::new(window.width(), window.height());
-                    // let diff = Vec3::new(vec.x - window.width()/2.0, vec.y - window.height()/2.0, 0.) - player.translation;
-                    let mut mouse_coords = vec;
-                    // flip the mouse Y position
-                    mouse_coords.y = mouse_coords.y * -1.0 + window_size.y;
-                    let diff = mouse_coords.extend(0.0)
-                        - window_size.extend(0.0) / 2.0
-                        - player.translation;
-                    let angle = diff.y.atan2(diff.x); // Add/sub FRAC_PI here optionally
-
-                    for child in children.iter() {
-                        if let Ok(tank_child) = tank_child_query.get_mut(*child)
-                        {
-                            for bearing in tank_child.iter() {
-                                if let Ok((mut joint, turrets)) =
-                                    bearings.get_mut(*bearing)
-                                {
-                                    joint.rotation =
-                                        Quat::from_rotation_z(angle);
-                                    for turret in turrets.iter() {
-                                        if let Ok(mut transform) =
-                                            transform_query.get_mut(*turret)
-                                        {
-                                            transform.translation.x +=
-                                                ((TANK_SIZE + 4.0)
-                                                    - transform.translation.x)
-                                                    * 0.1;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    if buttons.pressed(MouseButton::Left)
-                        && attack_timer.value > 0.4
-                        && LENGTH + FADE + 1.0 < time.elapsed_seconds() as f32
+
+    for (
+        player,
+        mut attack_timer,
+        weapon,
+        stats,
+        traverse,
+        children,
+        effects,
+        mut magazine,
+    ) in positions.iter_mut()
+    {
+        let diff = mouse_coords - player.translation.truncate();
+        let angle = diff.y.atan2(diff.x); // Add/sub FRAC_PI here optionally
+
+        // The turret turns towards the cursor as fast as it can, and can only
+        // fire once it is pointing close enough to it
+        let mut on_target = false;
+        let mut facing = diff;
+        for bearing in children.iter() {
+            if let Ok((mut joint, turrets)) = bearings.get_mut(*bearing) {
+                let current = joint.rotation.to_euler(EulerRot::ZYX).0;
+                let turned =
+                    traverse.turn(current, angle, time.delta_seconds());
+                joint.rotation = Quat::from_rotation_z(turned);
+                on_target = traverse.on_target(turned, angle);
+                facing = Vec2::from_angle(turned);
+                for turret in turrets.iter() {
+                    if let Ok(mut transform) = transform_query.get_mut(*turret)
                     {
-                        attack_timer.value = 0.0;
-                        if !MUTE {
-                            // Goofy ahh work around to world being exclusive
-                            commands.add(|world: &mut World| {
-                                world.run_system_once(play_gunshot)
-                            })
-                        }
+                        transform.translation.x += ((stats.radius
+                            + TURRET_REACH)
+                            - transform.translation.x)
+                            * 0.1;
+                    }
+                }
+            }
+        }
+        if buttons.pressed(MouseButton::Left)
+            && fire_readiness(
+                &attack_timer,
+                weapon,
+                effects,
+                magazine.as_deref(),
+            ) >= 1.0
+            && magazine.as_ref().is_none_or(|m| m.can_fire())
+            && on_target
+        {
+            attack_timer.value = 0.0;
+            if let Some(magazine) = magazine.as_mut() {
+                magazine.fire();
+            }
+            camera_effects.add_trauma(FIRE_TRAUMA);
+            camera_effects.kick(facing, FIRE_KICK);
+            if !MUTE {
+                // Goofy ahh work around to world being exclusive
+                commands.add(|world: &mut World| {
+                    world.run_system_once(play_gunshot)
+                })
+            }
 
-                        for child in children.iter() {
-                            if let Ok(tank_child) =
-                                tank_child_query.get_mut(*child)
-                            {
-                                for bearing in tank_child.iter() {
-                                    if let Ok((mut joint, turrets)) =
-                                        bearings.get_mut(*bearing)
-                                    {
-                                        joint.rotation =
-                                            Quat::from_rotation_z(angle);
-                                        for turret in turrets.iter() {
-                                            if let Ok(mut transform) =
-                                                transform_query.get_mut(*turret)
-                                            {
-                                                transform.translation.x =
-                                                    TANK_SIZE + 4.0 - 10.0;
-                                            }
-                                        }
-                                    }
-                                }
-                            }
+            for bearing in children.iter() {
+                if let Ok((_joint, turrets)) = bearings.get_mut(*bearing) {
+                    for turret in turrets.iter() {
+                        if let Ok(mut transform) =
+                            transform_query.get_mut(*turret)
+                        {
+                            transform.translation.x =
+                                stats.radius + TURRET_REACH - 10.0;
                         }
-
-                        commands.spawn((
-                            MaterialMesh2dBundle {
-                                mesh: Mesh2dHandle(meshes.add(Circle {
-                                    radius: BULLET_SIZE,
-                                })),
-                                material: materials
-                                    .add(ColorMaterial::from(Color::BLACK)),
-                                transform: Transform::from_translation(
-                                    Vec3::new(
-                                        player.translation.x,
-                                        player.translation.y,
-                                        0.0,
-                                    ),
-                                ),
-                                ..default()
-                            },
-                            Name::new("Bullet"),
-                            Bullet {
-                                from: TurretOf::Player,
-                            },
-                            Direction {
-                                dir: (mouse_coords
-                                    - player.translation.truncate()
-                                    - window_size / 2.0)
-                                    .normalize(),
-                            },
-                        ));
                     }
-
-                    attack_timer.value += time.delta_seconds()
                 }
             }
-            None => println!(
-                "Cursor outside of screen, but window is still in focus?"
-            ),
+
+            spawn_bullets(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                player.translation.truncate(),
+                facing.normalize_or_zero(),
+                TurretOf::Player,
+                effects,
+                weapon,
+                stats,
+            );
         }
+
+        attack_timer.value += time.delta_seconds()
     }
 }
 
@@ -686,13 +1315,19 @@ fn ai_rotate(
     players: Query<&Transform, (Without<Ai>, With<Player>)>,
     mut commands: Commands,
     mut positions: Query<
-        (&mut Transform, &mut AttackTimer, &Children, &mut Active),
+        (
+            &mut Transform,
+            &mut AttackTimer,
+            &Weapon,
+            &TankStats,
+            &TurretTraverse,
+            &Children,
+            &mut Active,
+            &ActiveEffects,
+            Option<&mut Magazine>,
+        ),
         With<Ai>,
     >,
-    mut tank_child_query: Query<
-        &Children,
-        (Without<Ai>, Without<Turret>, Without<Bearing>),
-    >,
     mut bearings: Query<
         (&mut Transform, &Children),
         (With<Bearing>, Without<Player>, Without<Ai>, Without<Turret>),
@@ -701,124 +1336,153 @@ fn ai_rotate(
         &mut Transform,
         (With<Turret>, Without<Ai>, Without<Player>),
     >,
+    bullets: Query<
+        (&Transform, &Direction, &Bullet),
+        (Without<Ai>, Without<Bearing>, Without<Turret>),
+    >,
+    settings: Res<Settings>,
 
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
-    for (ai, mut attack_timer, children, mut active) in positions.iter_mut() {
+    // On the harder difficulties, AI shoot down the player's bullets when they
+    // can
+    let intercept_range = settings
+        .difficulty
+        .intercept_range()
+        .filter(|_| settings.bullet_collisions);
+    for (
+        ai,
+        mut attack_timer,
+        weapon,
+        stats,
+        traverse,
+        children,
+        mut active,
+        effects,
+        mut magazine,
+    ) in positions.iter_mut()
+    {
         if active.value == true {
-            let mut player_count = 0;
-            for player in players.iter() {
+            // AI aim at the closest player they can see. There are only ever a
+            // few players, so this looks through them rather than the grid.
+            let position = ai.translation.truncate();
+            let target = players
+                .iter()
+                .map(|player| player.translation.truncate())
+                .filter(|player| player.distance(position) <= AI_SIGHT_RANGE)
+                .min_by(|a, b| {
+                    a.distance_squared(position)
+                        .total_cmp(&b.distance_squared(position))
+                });
+            // A bullet about to hit them comes first
+            let intercept = intercept_range.and_then(|range| {
+                incoming_bullet_intercept(
+                    ai.translation.truncate(),
+                    stats.radius,
+                    range,
+                    weapon.bullet_speed,
+                    bullets.iter(),
+                )
+            });
+            let aim = intercept.or(target);
+            if let Some(aim) = aim {
                 // let window_size = Vec2::new(window.width(), window.height());
-                let diff =
-                    Vec3::new(player.translation.x, player.translation.y, 0.)
-                        - ai.translation;
+                let diff = aim.extend(0.0) - ai.translation;
                 // let diff = vec.extend(0.0) - window_size.extend(0.0)/2.0 - ai.translation;
                 let angle = diff.y.atan2(diff.x); // Add/sub FRAC_PI here optionally
                                                   // ai.rotation = Quat::from_rotation_z(angle);
-                for child in children.iter() {
-                    if let Ok(tank_child) = tank_child_query.get_mut(*child) {
-                        for bearing in tank_child.iter() {
-                            if let Ok((mut joint, turrets)) =
-                                bearings.get_mut(*bearing)
+
+                // Like the player's, the AI's turret has to turn to face its
+                // target before firing
+                let mut on_target = false;
+                let mut facing = diff.truncate().normalize_or_zero();
+                for bearing in children.iter() {
+                    if let Ok((mut joint, turrets)) = bearings.get_mut(*bearing)
+                    {
+                        let current = joint.rotation.to_euler(EulerRot::ZYX).0;
+                        let turned =
+                            traverse.turn(current, angle, time.delta_seconds());
+                        joint.rotation = Quat::from_rotation_z(turned);
+                        on_target = traverse.on_target(turned, angle);
+                        facing = Vec2::from_angle(turned);
+                        for turret in turrets.iter() {
+                            if let Ok(mut transform) =
+                                transform_query.get_mut(*turret)
                             {
-                                joint.rotation = Quat::from_rotation_z(angle);
-                                for turret in turrets.iter() {
-                                    if let Ok(mut transform) =
-                                        transform_query.get_mut(*turret)
-                                    {
-                                        transform.translation.x += ((TANK_SIZE
-                                            + 4.0)
-                                            - transform.translation.x)
-                                            * 0.1;
-                                    }
-                                }
+                                transform.translation.x += ((stats.radius
+                                    + TURRET_REACH)
+                                    - transform.translation.x)
+                                    * 0.1;
                             }
                         }
                     }
                 }
 
                 if attack_timer.value < 0.0
-                    && LENGTH + FADE + 1.0 < time.elapsed_seconds() as f32
+                    && magazine.as_ref().is_none_or(|m| m.can_fire())
+                    && on_target
                 {
-                    attack_timer.value =
-                        rand::thread_rng().gen_range(5..=14) as f32 / 10.0;
+                    if let Some(magazine) = magazine.as_mut() {
+                        magazine.fire();
+                    }
+                    // AI wait a random multiple of their weapon's cooldown
+                    // between shots
+                    attack_timer.value = weapon.cooldown
+                        * rand::thread_rng().gen_range(1.25..=3.5);
                     if !MUTE {
                         // Goofy ahh work around to world being exclusive
                         commands.add(|world: &mut World| {
                             world.run_system_once(play_gunshot)
                         })
                     }
-                    for child in children.iter() {
-                        if let Ok(tank_child) = tank_child_query.get_mut(*child)
+                    for bearing in children.iter() {
+                        if let Ok((_joint, turrets)) =
+                            bearings.get_mut(*bearing)
                         {
-                            for bearing in tank_child.iter() {
-                                if let Ok((mut joint, turrets)) =
-                                    bearings.get_mut(*bearing)
+                            for turret in turrets.iter() {
+                                if let Ok(mut transform) =
+                                    transform_query.get_mut(*turret)
                                 {
-                                    joint.rotation =
-                                        Quat::from_rotation_z(angle);
-                                    for turret in turrets.iter() {
-                                        if let Ok(mut transform) =
-                                            transform_query.get_mut(*turret)
-                                        {
-                                            transform.translation.x =
-                                                TANK_SIZE + 4.0 - 10.0;
-                                        }
-                                    }
+                                    transform.translation.x =
+                                        stats.radius + TURRET_REACH - 10.0;
                                 }
                             }
                         }
                     }
-                    commands.spawn((
-                        MaterialMesh2dBundle {
-                            mesh: Mesh2dHandle(meshes.add(Circle {
-                                radius: BULLET_SIZE,
-                            })),
-                            material: materials
-                                .add(ColorMaterial::from(Color::BLACK)),
-                            transform: Transform::from_translation(Vec3::new(
-                                ai.translation.x,
-                                ai.translation.y,
-                                0.0,
-                            )),
-                            ..default()
-                        },
-                        Name::new("Bullet"),
-                        Bullet { from: TurretOf::Ai },
-                        Direction {
-                            dir: (player.translation.truncate()
-                                - ai.translation.truncate())
-                            .normalize(),
-                        },
-                    ));
+                    spawn_bullets(
+                        &mut commands,
+                        &mut meshes,
+                        &mut materials,
+                        ai.translation.truncate(),
+                        facing,
+                        TurretOf::Ai,
+                        effects,
+                        weapon,
+                        stats,
+                    );
                 }
 
-                if LENGTH + FADE + 1.0 < time.elapsed_seconds() as f32 {
-                    attack_timer.value -= time.delta_seconds();
-                }
-                player_count += 1;
+                // Rapid fire makes the cooldown run down faster, and AI on
+                // easier difficulties take longer between shots
+                attack_timer.value -= time.delta_seconds()
+                    * settings.difficulty.fire_rate()
+                    / effects.cooldown_multiplier();
             }
-            if player_count == 0 {
+            if players.is_empty() {
                 active.value = false;
             }
         } else {
-            for child in children.iter() {
-                if let Ok(tank_child) = tank_child_query.get_mut(*child) {
-                    for bearing in tank_child.iter() {
-                        if let Ok((_joint, turrets)) =
-                            bearings.get_mut(*bearing)
+            for bearing in children.iter() {
+                if let Ok((_joint, turrets)) = bearings.get_mut(*bearing) {
+                    for turret in turrets.iter() {
+                        if let Ok(mut transform) =
+                            transform_query.get_mut(*turret)
                         {
-                            for turret in turrets.iter() {
-                                if let Ok(mut transform) =
-                                    transform_query.get_mut(*turret)
-                                {
-                                    transform.translation.x += ((TANK_SIZE
-                                        + 4.0)
-                                        - transform.translation.x)
-                                        * 0.1;
-                                }
-                            }
+                            transform.translation.x += ((stats.radius
+                                + TURRET_REACH)
+                                - transform.translation.x)
+                                * 0.1;
                         }
                     }
                 }
@@ -830,144 +1494,434 @@ fn ai_rotate(
     }
 }
 
+// Fires from `position` in `direction`, taking the shooter's power-ups into
+// account
+fn spawn_bullets(
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    position: Vec2,
+    direction: Vec2,
+    from: TurretOf,
+    effects: &ActiveEffects,
+    weapon: &Weapon,
+    stats: &TankStats,
+) {
+    // A triple shot fires two extra bullets angled either side of the aim
+    let angles = if effects.has(PowerUp::TripleShot) {
+        vec![-TRIPLE_SHOT_SPREAD, 0.0, TRIPLE_SHOT_SPREAD]
+    } else {
+        vec![0.0]
+    };
+    let bounces = if effects.has(PowerUp::BouncingBullets) {
+        BULLET_BOUNCES
+    } else {
+        0
+    };
+    spawn_burst(
+        commands,
+        &MUZZLE_SMOKE,
+        position + direction * (stats.radius + MUZZLE_REACH),
+        direction,
+    );
+
+    for angle in angles {
+        commands.spawn((
+            MaterialMesh2dBundle {
+                mesh: Mesh2dHandle(meshes.add(Circle {
+                    radius: weapon.bullet_size,
+                })),
+                material: materials.add(ColorMaterial::from(Color::BLACK)),
+                transform: Transform::from_translation(position.extend(0.0)),
+                ..default()
+            },
+            Name::new("Bullet"),
+            Interpolated::default(),
+            Bullet {
+                from,
+                damage: weapon.bullet_damage,
+                speed: weapon.bullet_speed,
+                size: weapon.bullet_size,
+                bounces,
+            },
+            Direction {
+                dir: Vec2::from_angle(angle).rotate(direction),
+            },
+        ));
+    }
+}
+
+// Bullets that hit a tank in update_bullets are marked with BulletHit, and are
+// dealt with here
 fn hurt_tanks(
     mut commands: Commands,
-    bullets: Query<
-        (&Transform, Entity, &Bullet),
-        (Without<Player>, Without<Ai>, With<Bullet>),
-    >,
-    mut ais: Query<
-        (
-            &Transform,
-            Entity,
-            &mut Health,
-            &mut Velocity,
-            &mut HitTimer,
-        ),
-        (Without<Player>, With<Ai>, Without<Bullet>),
-    >,
-    mut players: Query<
+    bullets: Query<(&Transform, Entity, &Bullet, &BulletHit), Without<Tank>>,
+    mut tanks: Query<
         (
-            &mut Transform,
             Entity,
             &mut Health,
             &mut Velocity,
+            &TankStats,
             &mut HitTimer,
+            Has<Ai>,
+            Has<Invulnerable>,
+            &ActiveEffects,
+            Option<&mut Shield>,
         ),
-        (With<Player>, Without<Ai>, Without<Bullet>),
+        (With<Tank>, Without<Bullet>),
     >,
+    mut died: EventWriter<Died>,
+    mut camera_effects: ResMut<CameraEffects>,
+) {
+    for (bullet_transform, bullet_entity, bullet, hit) in bullets.iter() {
+        commands.entity(bullet_entity).despawn();
+        // The tank might have been destroyed since the bullet hit it
+        let Ok((
+            entity,
+            mut health,
+            mut velocity,
+            stats,
+            mut hit_timer,
+            is_ai,
+            invulnerable,
+            effects,
+            shield,
+        )) = tanks.get_mut(hit.tank)
+        else {
+            continue;
+        };
+
+        // The bullet's momentum is handed over to the tank, so heavy, fast
+        // bullets shove harder and heavy tanks are shoved less
+        let knockback = -hit.normal * bullet.momentum() / stats.mass;
+        velocity.value += knockback;
+        // Sparks fly back the way the bullet came from, from where it touched
+        // the tank
+        spawn_burst(
+            &mut commands,
+            &SPARKS,
+            bullet_transform.translation.truncate() - hit.normal * bullet.size,
+            hit.normal,
+        );
+
+        // Invulnerable and shielded tanks still absorb the bullet, but don't
+        // take damage or flash
+        if invulnerable || effects.has(PowerUp::Shield) {
+            continue;
+        }
+        let (damage, shielded) = damage_tank(
+            entity,
+            bullet.damage,
+            &mut health,
+            shield,
+            &mut hit_timer,
+            &mut died,
+        );
+        hit_feedback(
+            &mut commands,
+            &mut camera_effects,
+            is_ai,
+            damage,
+            shielded,
+        );
+    }
+}
+
+// Deals damage to a tank, letting its shield take what it can first. Returns
+// how much got through to the health, and whether the shield stopped any of it.
+fn damage_tank(
+    entity: Entity,
+    amount: f32,
+    health: &mut Health,
+    shield: Option<Mut<Shield>>,
+    hit_timer: &mut HitTimer,
+    died: &mut EventWriter<Died>,
+) -> (f32, bool) {
+    hit_timer.0 = 0.0;
+    let (damage, shielded) = match shield {
+        Some(mut shield) => {
+            let damage = shield.absorb(amount);
+            (damage, shield.absorbed_last_hit)
+        }
+        None => (amount, false),
+    };
+    if health.damage(damage) {
+        died.send(Died { entity });
+    }
+    (damage, shielded)
+}
+
+// Shakes the screen and plays a sound for a tank being hit
+fn hit_feedback(
+    commands: &mut Commands,
+    camera_effects: &mut CameraEffects,
+    is_ai: bool,
+    damage: f32,
+    shielded: bool,
+) {
+    // Only the player's screen shakes when they are hit
+    if !is_ai {
+        camera_effects.add_trauma(if shielded && damage == 0.0 {
+            SHIELD_HIT_TRAUMA
+        } else {
+            DAMAGE_TRAUMA
+        });
+    }
+    if !MUTE {
+        // Goofy ahh work around to world being exclusive
+        if shielded {
+            commands
+                .add(|world: &mut World| world.run_system_once(play_shieldhit))
+        } else {
+            commands
+                .add(|world: &mut World| world.run_system_once(play_tankhit))
+        }
+    }
+}
+
+// Removes tanks that have run out of health, and counts the AI the player has
+// killed
+fn handle_deaths(
+    mut commands: Commands,
+    mut died: EventReader<Died>,
+    ais: Query<(), With<Ai>>,
     mut ai_killed: ResMut<AiKilled>,
+    mut camera_effects: ResMut<CameraEffects>,
+    transforms: Query<&Transform>,
+    scorch_mesh: Res<ScorchMesh>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
-    for (bullet_transform, bullet_entity, bullet_type) in bullets.iter() {
-        match bullet_type.from {
-            TurretOf::Player => {
-                for (
-                    ai_transform,
-                    ai_entity,
-                    mut ai_health,
-                    mut velocity,
-                    mut hit_timer,
-                ) in ais.iter_mut()
-                {
-                    if (ai_transform.translation.truncate()
-                        - bullet_transform.translation.truncate())
-                    .length()
-                        < TANK_SIZE + BULLET_SIZE
-                    {
-                        let knockback = (ai_transform.translation
-                            - bullet_transform.translation)
-                            .truncate()
-                            .normalize()
-                            * KNOCKBACK;
-                        velocity.value += knockback;
-                        hit_timer.0 = 0.0;
-
-                        if ai_health.value > 1 {
-                            ai_health.value -= 1;
-                        } else {
-                            commands.entity(ai_entity).despawn_recursive();
-                            ai_killed.score += 1;
-                        }
-                        commands.entity(bullet_entity).despawn();
-                        if !MUTE {
-                            // Goofy ahh work around to world being exclusive
-                            commands.add(|world: &mut World| {
-                                world.run_system_once(play_tankhit)
-                            })
-                        }
+    for event in died.read() {
+        if let Ok(transform) = transforms.get(event.entity) {
+            spawn_tank_explosion(
+                &mut commands,
+                &scorch_mesh,
+                &mut materials,
+                transform.translation.truncate(),
+            );
+        }
+        if ais.contains(event.entity) {
+            ai_killed.score += 1;
+        }
+        camera_effects.add_trauma(EXPLOSION_TRAUMA);
+        camera_effects.hit_stop(KILL_HIT_STOP);
+        commands.entity(event.entity).despawn_recursive();
+        if !MUTE {
+            commands
+                .add(|world: &mut World| world.run_system_once(play_explosion))
+        }
+    }
+}
+
+// Moves bullets along their path for this tick, sweeping them along it so that
+// fast bullets can't pass through a tank or wall between one tick and the next.
+// Bullets stop where they first hit a tank, and bounce off (or are stopped by)
+// the walls.
+fn update_bullets(
+    mut commands: Commands,
+    arena: Res<Arena>,
+    grid: Res<TankGrid>,
+    mut bullets: Query<
+        (Entity, &mut Transform, &mut Direction, &mut Bullet),
+        Without<BulletHit>,
+    >,
+    tanks: Query<(&Transform, &TankStats, Has<Ai>), Without<Bullet>>,
+) {
+    let walls = arena.walls();
+    for (bullet_entity, mut transform, mut direction, mut bullet) in
+        bullets.iter_mut()
+    {
+        let mut position = transform.translation.truncate();
+        let mut motion = direction.dir * bullet.speed;
+        // Bullets can bounce more than once in a tick when they go into a
+        // corner
+        loop {
+            let reach = motion.length() / 2.0 + bullet.size;
+            let tank_hit = grid
+                .nearby(position + motion / 2.0, reach)
+                .filter_map(|entry| {
+                    let (tank, stats, is_ai) = tanks.get(entry.entity).ok()?;
+                    if !bullet.from.can_hit(is_ai) {
+                        return None;
                     }
-                }
-            }
-            TurretOf::Ai => {
-                for (
-                    player_transform,
-                    player_entity,
-                    mut player_health,
-                    mut velocity,
-                    mut hit_timer,
-                ) in players.iter_mut()
+                    sweep_circle_circle(
+                        position,
+                        motion,
+                        bullet.size,
+                        tank.translation.truncate(),
+                        stats.radius,
+                    )
+                    .map(|hit| (hit, entry.entity))
+                })
+                .min_by(|(a, _), (b, _)| a.time.total_cmp(&b.time));
+            let wall_hit = walls
+                .iter()
+                .filter_map(|wall| {
+                    sweep_circle_rect(position, motion, bullet.size, *wall)
+                })
+                .min_by(|a, b| a.time.total_cmp(&b.time));
+
+            match (tank_hit, wall_hit) {
+                (Some((hit, tank)), wall_hit)
+                    if wall_hit.is_none_or(|wall| hit.time <= wall.time) =>
                 {
-                    if (player_transform.translation.truncate()
-                        - bullet_transform.translation.truncate())
-                    .length()
-                        < TANK_SIZE + BULLET_SIZE
-                    {
-                        let knockback = (player_transform.translation
-                            - bullet_transform.translation)
-                            .truncate()
-                            .normalize()
-                            * KNOCKBACK;
-                        velocity.value += knockback;
-                        hit_timer.0 = 0.0;
-
-                        if player_health.value > 1 {
-                            player_health.value -= 1;
-                        } else {
-                            commands.entity(player_entity).despawn_recursive();
-                            ai_killed.score += 0;
-                        }
+                    position = hit.position;
+                    commands.entity(bullet_entity).insert(BulletHit {
+                        tank,
+                        normal: hit.normal,
+                    });
+                    break;
+                }
+                (_, Some(hit)) => {
+                    position = hit.position;
+                    // The dust is thrown back out of the wall, into the arena
+                    spawn_burst(
+                        &mut commands,
+                        &DUST,
+                        position - hit.normal * bullet.size,
+                        hit.normal,
+                    );
+                    if !MUTE {
+                        // Goofy ahh work around to world being exclusive
+                        commands.add(|world: &mut World| {
+                            world.run_system_once(play_wallhit)
+                        })
+                    }
+                    if bullet.bounces == 0 {
                         commands.entity(bullet_entity).despawn();
-                        if !MUTE {
-                            // Goofy ahh work around to world being exclusive
-                            commands.add(|world: &mut World| {
-                                world.run_system_once(play_tankhit)
-                            })
-                        }
+                        break;
                     }
+                    // Bouncing bullets are reflected off the wall, and carry on
+                    // with the rest of their movement
+                    bullet.bounces -= 1;
+                    direction.dir = reflect(direction.dir, hit.normal);
+                    motion = reflect(motion * (1.0 - hit.time), hit.normal);
+                }
+                _ => {
+                    position += motion;
+                    break;
                 }
             }
         }
+        transform.translation = position.extend(transform.translation.z);
     }
 }
 
-fn update_bullets(
-    mut bullets: Query<(&mut Transform, &Direction), With<Bullet>>,
+// When bullet collisions are on, bullets from opposing sides that touch knock
+// each other out of the air. They are swept against each other like bullets
+// against tanks, so fast bullets can't pass through each other between ticks.
+fn collide_bullets(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    bullets: Query<
+        (Entity, &Transform, &Direction, &Bullet),
+        Without<BulletHit>,
+    >,
 ) {
-    for (mut transform, direction) in bullets.iter_mut() {
-        transform.translation.x += direction.dir.x * 10.;
-        transform.translation.y += direction.dir.y * 10.;
+    if !settings.bullet_collisions {
+        return;
     }
-}
 
-fn kill_bullets(
-    mut commands: Commands,
-    mut bullets: Query<(&mut Transform, Entity), With<Bullet>>,
-) {
-    for (transform, bullet_entity) in bullets.iter_mut() {
-        if transform.translation.x.abs() > GAME_WIDTH / 2.
-            || transform.translation.y.abs() > GAME_HEIGHT / 2.
-        {
-            commands.entity(bullet_entity).despawn();
+    // Each bullet is filed as big as everywhere it could get to this tick
+    let mut grid = SpatialGrid::new(TANK_GRID_CELL_SIZE);
+    for (entity, transform, _, bullet) in bullets.iter() {
+        grid.insert(
+            entity,
+            transform.translation.truncate(),
+            bullet.size + bullet.speed,
+        );
+    }
+
+    let mut destroyed = HashSet::new();
+    for (entity, transform, direction, bullet) in bullets.iter() {
+        if destroyed.contains(&entity) {
+            continue;
+        }
+        let position = transform.translation.truncate();
+        let motion = direction.dir * bullet.speed;
+        let hit = grid
+            .nearby(position, bullet.size + bullet.speed)
+            .filter(|entry| !destroyed.contains(&entry.entity))
+            .filter_map(|entry| {
+                let (other, other_transform, other_direction, other_bullet) =
+                    bullets.get(entry.entity).ok()?;
+                if other_bullet.from == bullet.from {
+                    return None;
+                }
+                // Both are moving, so one is swept along how it moves relative
+                // to the other
+                let other_motion = other_direction.dir * other_bullet.speed;
+                sweep_circle_circle(
+                    position,
+                    motion - other_motion,
+                    bullet.size,
+                    other_transform.translation.truncate(),
+                    other_bullet.size,
+                )
+                .map(|hit| (hit, other))
+            })
+            .min_by(|(a, _), (b, _)| a.time.total_cmp(&b.time));
+
+        if let Some((hit, other)) = hit {
+            destroyed.insert(entity);
+            destroyed.insert(other);
+            commands.entity(entity).despawn();
+            commands.entity(other).despawn();
+            // Sparks fly out sideways from where they met
+            let contact =
+                position + motion * hit.time - hit.normal * bullet.size;
+            spawn_burst(&mut commands, &SPARKS, contact, hit.normal.perp());
+            spawn_burst(&mut commands, &SPARKS, contact, -hit.normal.perp());
             if !MUTE {
                 // Goofy ahh work around to world being exclusive
                 commands.add(|world: &mut World| {
-                    world.run_system_once(play_wallhit)
+                    world.run_system_once(play_bullet_clash)
                 })
             }
         }
     }
 }
 
+// Where an AI should aim to shoot down the player bullet that will reach it
+// soonest, if any are heading close enough to hit it
+fn incoming_bullet_intercept<'a>(
+    position: Vec2,
+    radius: f32,
+    range: f32,
+    bullet_speed: f32,
+    bullets: impl Iterator<Item = (&'a Transform, &'a Direction, &'a Bullet)>,
+) -> Option<Vec2> {
+    bullets
+        // Only bullets that can hit AI are worth shooting down
+        .filter(|(_, _, bullet)| bullet.from.can_hit(true))
+        .filter_map(|(transform, direction, bullet)| {
+            let from = transform.translation.truncate();
+            let offset = position - from;
+            let along = offset.dot(direction.dir);
+            let miss_by = offset.perp_dot(direction.dir).abs();
+            (along > 0.0
+                && offset.length() < range
+                && miss_by < radius + bullet.size)
+                .then_some((
+                    from,
+                    direction.dir * bullet.speed,
+                    along / bullet.speed,
+                ))
+        })
+        .min_by(|(_, _, a), (_, _, b)| a.total_cmp(b))
+        .map(|(from, velocity, _)| {
+            // Lead the bullet by roughly how long it will take to meet it head
+            // on
+            let meet_in =
+                from.distance(position) / (velocity.length() + bullet_speed);
+            from + velocity * meet_in
+        })
+}
+
+fn reflect(vector: Vec2, normal: Vec2) -> Vec2 {
+    vector - 2.0 * vector.dot(normal) * normal
+}
+
 // fn toggle_inspector(
 //     input: ResMut<Input<KeyCode>>,
 //     mut window_params: ResMut<WorldInspectorParams>,
@@ -984,11 +1938,13 @@ fn button_system(
     >,
     mut commands: Commands,
     active_ai: Query<&mut Active>,
-    text_query: Query<&mut Text>,
+    mut text_query: Query<&mut Text>,
     mut windows: Query<&mut Window>,
+    mut settings: ResMut<Settings>,
+    mut next_round: ResMut<NextState<RoundState>>,
 ) {
     for (interaction, mut color, children) in &mut interaction_query {
-        let text = text_query.get(children[0]).unwrap();
+        let mut text = text_query.get_mut(children[0]).unwrap();
         match *interaction {
             Interaction::Pressed => {
                 if text.sections[0].value == "Spawn Player".to_string() {
@@ -1002,7 +1958,9 @@ fn button_system(
                     if no_players {
                         commands.add(|world: &mut World| {
                             world.run_system_once(create_player);
-                        })
+                        });
+                        // A new player starts a new round
+                        next_round.set(RoundState::Countdown);
                     }
                 }
                 if text.sections[0].value == "Spawn AI".to_string() {
@@ -1010,6 +1968,24 @@ fn button_system(
                         world.run_system_once(create_enemy);
                     })
                 }
+                if text.sections[0].value
+                    == difficulty_label(settings.difficulty)
+                {
+                    settings.difficulty = settings.difficulty.next();
+                    text.sections[0].value =
+                        difficulty_label(settings.difficulty);
+                }
+                if text.sections[0].value == ammo_label(settings.limited_ammo) {
+                    settings.limited_ammo = !settings.limited_ammo;
+                    text.sections[0].value = ammo_label(settings.limited_ammo);
+                }
+                if text.sections[0].value
+                    == bullet_clash_label(settings.bullet_collisions)
+                {
+                    settings.bullet_collisions = !settings.bullet_collisions;
+                    text.sections[0].value =
+                        bullet_clash_label(settings.bullet_collisions);
+                }
             }
             Interaction::Hovered => {
                 *color = Color::srgb(0.2, 0.2, 0.2).into();
@@ -1131,84 +2107,59 @@ fn unpause_system(
     }
 }
 
-// fn on_resise_system(
-//     mut resize_reader: EventReader<WindowResized>,
-// ) {
-//     for event in resize_reader.read() {
-//         // When resolution is being changed
-//         // text.sections[0].value = format!("{:.1} x {:.1}", e.width, e.height);
-//         // zoom = math.min(w/screenWidth, h/screenHeight)
-
-//     }
-// }
-
 fn update_hit_timer(time: Res<Time>, mut hit_timers: Query<&mut HitTimer>) {
     for mut hit_timer in hit_timers.iter_mut() {
         hit_timer.0 += time.delta_seconds()
     }
 }
 
-fn flash_yellow(
-    tank: Query<
-        (
-            &HitTimer,
-            &OriginalColour,
-            &Handle<ColorMaterial>,
-            &Children,
-        ),
-        (With<Tank>, Without<Turret>, Without<Bearing>),
-    >,
-    mut tank_child_query: Query<
-        (&Children, &OriginalColour, &Handle<ColorMaterial>),
-        (Without<Tank>, Without<Turret>, Without<Bearing>),
-    >,
-    mut bearings: Query<
-        &Children,
-        (With<Bearing>, Without<Tank>, Without<Turret>),
-    >,
-    mut turret_query: Query<
-        (&OriginalColour, &mut Sprite),
-        (With<Turret>, Without<Player>),
-    >,
-    mut materials: ResMut<Assets<ColorMaterial>>,
+fn regenerate_health(
+    time: Res<Time>,
+    mut tanks: Query<(&mut Health, &HitTimer, &Regeneration)>,
 ) {
-    for (hit_timer, original_colour, material_handle, children) in tank.iter() {
-        let material = materials.get_mut(material_handle.id()).unwrap();
+    for (mut health, hit_timer, regeneration) in tanks.iter_mut() {
+        if hit_timer.0 > regeneration.delay {
+            health.heal(regeneration.rate * time.delta_seconds());
+        }
+    }
+}
 
-        if hit_timer.0 < 1.0 / 15.0 {
-            material.color = Color::srgb(1.0, 1.0, 0.0);
-        } else {
-            material.color = original_colour.0;
+fn recharge_shields(
+    time: Res<Time>,
+    mut shields: Query<(&mut Shield, &HitTimer)>,
+) {
+    for (mut shield, hit_timer) in shields.iter_mut() {
+        if hit_timer.0 > shield.recharge_delay {
+            let amount = shield.recharge_rate * time.delta_seconds();
+            shield.recharge(amount);
         }
+    }
+}
 
-        // This isn't pretty, as any alteration to the tank's hierachy would break it
-        for child in children.iter() {
-            if let Ok((tank_child_children, original_colour, material_handle)) =
-                tank_child_query.get_mut(*child)
-            {
+// Tanks flash yellow when they take damage, or pale blue when their shield
+// stops the hit
+fn flash_yellow(
+    tanks: Query<(Entity, &HitTimer, Option<&Shield>), With<Tank>>,
+    children: Query<&Children>,
+    parts: Query<(&OriginalColour, &Handle<ColorMaterial>)>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for (tank, hit_timer, shield) in tanks.iter() {
+        let flash_colour = match shield {
+            Some(shield) if shield.absorbed_last_hit => SHIELD_FLASH_COLOUR,
+            _ => Color::srgb(1.0, 1.0, 0.0),
+        };
+
+        // Every part of the tank with an original colour flashes
+        for part in children.iter_descendants(tank) {
+            if let Ok((original_colour, material_handle)) = parts.get(part) {
                 let material = materials.get_mut(material_handle.id()).unwrap();
 
                 if hit_timer.0 < 1.0 / 15.0 {
-                    material.color = Color::srgb(1.0, 1.0, 0.0);
+                    material.color = flash_colour;
                 } else {
                     material.color = original_colour.0;
                 }
-
-                for bearing in tank_child_children.iter() {
-                    if let Ok(bearing_child) = bearings.get_mut(*bearing) {
-                        for turret in bearing_child.iter() {
-                            if let Ok((original_colour, mut material)) =
-                                turret_query.get_mut(*turret)
-                            {
-                                if hit_timer.0 < 1.0 / 15.0 {
-                                    material.color = Color::srgb(1.0, 1.0, 0.0);
-                                } else {
-                                    material.color = original_colour.0;
-                                }
-                            }
-                        }
-                    }
-                }
             }
         }
     }
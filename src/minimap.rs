@@ -0,0 +1,206 @@
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+
+use crate::arena::Arena;
+use crate::camera::{visible_area, FollowCamera};
+use crate::pickups::{AmmoCrate, PowerUpPickup, RepairKit};
+use crate::tanks::{Ai, Player, Tank};
+
+// A map of the whole arena in the corner of the screen. Its border is the
+// arena's walls, tanks are shown in their team's colour, pickups in theirs, and
+// an outline shows what the camera can currently see.
+
+// The minimap's width in (UI) pixels. Its height comes from the arena's shape.
+const MINIMAP_WIDTH: f32 = 160.0;
+const MINIMAP_MARGIN: f32 = 12.0;
+const MINIMAP_WALL_THICKNESS: f32 = 2.0;
+
+const TANK_DOT_SIZE: f32 = 6.0;
+const PICKUP_DOT_SIZE: f32 = 4.0;
+
+const PLAYER_DOT_COLOUR: Color = Color::srgb(0.35, 0.6, 0.99);
+const AI_DOT_COLOUR: Color = Color::srgb(0.89, 0.56, 0.26);
+const REPAIR_KIT_DOT_COLOUR: Color = Color::srgb(0.95, 0.95, 0.95);
+const AMMO_CRATE_DOT_COLOUR: Color = Color::srgb(0.95, 0.8, 0.25);
+
+#[derive(Component)]
+pub struct Minimap {
+    size: Vec2,
+}
+
+// The outline of the camera's view
+#[derive(Component)]
+pub struct MinimapView;
+
+// A dot on the minimap following `target` around
+#[derive(Component)]
+pub struct MinimapDot {
+    target: Entity,
+}
+
+pub fn spawn_minimap(mut commands: Commands, arena: Res<Arena>) {
+    let size =
+        Vec2::new(MINIMAP_WIDTH, MINIMAP_WIDTH * arena.size.y / arena.size.x);
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    right: Val::Px(MINIMAP_MARGIN),
+                    bottom: Val::Px(MINIMAP_MARGIN),
+                    // The walls are drawn outside of the map, so they don't
+                    // cover anything up
+                    width: Val::Px(size.x + MINIMAP_WALL_THICKNESS * 2.0),
+                    height: Val::Px(size.y + MINIMAP_WALL_THICKNESS * 2.0),
+                    border: UiRect::all(Val::Px(MINIMAP_WALL_THICKNESS)),
+                    overflow: Overflow::clip(),
+                    ..default()
+                },
+                background_color: Color::srgba(0.0, 0.0, 0.0, 0.45).into(),
+                border_color: Color::srgb(0.9, 0.9, 0.9).into(),
+                ..default()
+            },
+            Minimap { size },
+            Name::new("Minimap"),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        border: UiRect::all(Val::Px(1.0)),
+                        ..default()
+                    },
+                    border_color: Color::srgba(1.0, 1.0, 1.0, 0.4).into(),
+                    ..default()
+                },
+                MinimapView,
+            ));
+        });
+}
+
+// Converts a point in the arena to a point on the minimap, measured from its
+// top left corner
+fn to_minimap(position: Vec2, arena: &Arena, minimap_size: Vec2) -> Vec2 {
+    let fraction = position / arena.size + Vec2::splat(0.5);
+    Vec2::new(fraction.x, 1.0 - fraction.y) * minimap_size
+}
+
+// Gives everything that should be on the minimap a dot, moves the dots to where
+// their targets are, and removes the dots of anything that is gone
+pub fn update_minimap(
+    mut commands: Commands,
+    arena: Res<Arena>,
+    minimaps: Query<(Entity, &Minimap)>,
+    targets: Query<
+        (
+            Entity,
+            &Transform,
+            Has<Player>,
+            Has<Ai>,
+            Has<RepairKit>,
+            Has<AmmoCrate>,
+            Option<&PowerUpPickup>,
+        ),
+        Or<(
+            With<Tank>,
+            With<RepairKit>,
+            With<AmmoCrate>,
+            With<PowerUpPickup>,
+        )>,
+    >,
+    mut dots: Query<(Entity, &MinimapDot, &mut Style), Without<MinimapView>>,
+    mut views: Query<&mut Style, (With<MinimapView>, Without<MinimapDot>)>,
+    cameras: Query<(&Transform, &OrthographicProjection), With<FollowCamera>>,
+) {
+    let Ok((minimap_entity, minimap)) = minimaps.get_single() else {
+        return;
+    };
+
+    let mut has_dot = HashSet::new();
+    for (dot_entity, dot, mut style) in dots.iter_mut() {
+        match targets.get(dot.target) {
+            Ok((_, transform, ..)) => {
+                let size = match style.width {
+                    Val::Px(size) => size,
+                    _ => 0.0,
+                };
+                let position = to_minimap(
+                    transform.translation.truncate(),
+                    &arena,
+                    minimap.size,
+                );
+                style.left = Val::Px(position.x - size / 2.0);
+                style.top = Val::Px(position.y - size / 2.0);
+                has_dot.insert(dot.target);
+            }
+            Err(_) => commands.entity(dot_entity).despawn_recursive(),
+        }
+    }
+
+    for (
+        entity,
+        transform,
+        is_player,
+        is_ai,
+        is_repair_kit,
+        is_ammo_crate,
+        power_up,
+    ) in targets.iter()
+    {
+        if has_dot.contains(&entity) {
+            continue;
+        }
+        let (colour, size) = if is_player {
+            (PLAYER_DOT_COLOUR, TANK_DOT_SIZE)
+        } else if is_ai {
+            (AI_DOT_COLOUR, TANK_DOT_SIZE)
+        } else if is_repair_kit {
+            (REPAIR_KIT_DOT_COLOUR, PICKUP_DOT_SIZE)
+        } else if is_ammo_crate {
+            (AMMO_CRATE_DOT_COLOUR, PICKUP_DOT_SIZE)
+        } else if let Some(power_up) = power_up {
+            (power_up.0.colour(), PICKUP_DOT_SIZE)
+        } else {
+            continue;
+        };
+        let position =
+            to_minimap(transform.translation.truncate(), &arena, minimap.size);
+        // Tanks are drawn on top of pickups
+        let layer = if is_player || is_ai { 2 } else { 1 };
+        let dot = commands
+            .spawn((
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        left: Val::Px(position.x - size / 2.0),
+                        top: Val::Px(position.y - size / 2.0),
+                        width: Val::Px(size),
+                        height: Val::Px(size),
+                        ..default()
+                    },
+                    background_color: colour.into(),
+                    border_radius: BorderRadius::MAX,
+                    z_index: ZIndex::Local(layer),
+                    ..default()
+                },
+                MinimapDot { target: entity },
+            ))
+            .id();
+        commands.entity(minimap_entity).add_child(dot);
+    }
+
+    if let Ok((camera, projection)) = cameras.get_single() {
+        let view = visible_area(camera, projection);
+        let top_left =
+            to_minimap(Vec2::new(view.min.x, view.max.y), &arena, minimap.size);
+        let bottom_right =
+            to_minimap(Vec2::new(view.max.x, view.min.y), &arena, minimap.size);
+        for mut style in views.iter_mut() {
+            style.left = Val::Px(top_left.x);
+            style.top = Val::Px(top_left.y);
+            style.width = Val::Px(bottom_right.x - top_left.x);
+            style.height = Val::Px(bottom_right.y - top_left.y);
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use bevy::asset::LoadState;
+use bevy::audio::Volume;
+use bevy::prelude::*;
+
+use crate::settings::Settings;
+use crate::synth::SynthSounds;
+use crate::tanks::{Active, Ai, Player};
+use crate::utils::Health;
+use crate::{AppState, Bullet, TurretOf, MUTE};
+
+// The music is made of two layers that play in sync: a calm layer and a combat layer. Both loop
+// forever, and rather than switching tracks we crossfade between them depending on how intense the
+// game currently is. The tracks are loaded from the (embedded) assets folder, and a layer whose
+// track can't be loaded plays a synthesized loop instead (see synth.rs).
+const CALM_TRACK: &str = "music/Calm.ogg";
+const COMBAT_TRACK: &str = "music/Combat.ogg";
+
+// How quickly the intensity follows its target, per second. Low values give slow, smooth crossfades.
+const INTENSITY_FOLLOW_SPEED: f32 = 0.8;
+// Enemy bullets closer than this to the player count as "nearby"
+const DANGER_RADIUS: f32 = 200.0;
+// How much quieter the music gets while the game is paused
+const PAUSE_DUCK: f32 = 0.35;
+
+#[derive(Component)]
+pub enum MusicLayer {
+    Calm,
+    Combat,
+}
+
+// 0.0 is completely calm, 1.0 is full combat
+#[derive(Resource, Default)]
+pub struct MusicIntensity(pub f32);
+
+// The tracks while they load. The layers are only started once both have finished loading (or
+// failed to), so that they start in sync.
+#[derive(Resource)]
+pub struct LoadingMusic {
+    calm: Handle<AudioSource>,
+    combat: Handle<AudioSource>,
+}
+
+pub fn start_music(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(LoadingMusic {
+        calm: asset_server.load(CALM_TRACK),
+        combat: asset_server.load(COMBAT_TRACK),
+    });
+}
+
+// Starts the layers at zero volume once their tracks have loaded. The crossfade system fades them
+// in on the first frames.
+pub fn spawn_music_layers(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    loading: Res<LoadingMusic>,
+    synth: Res<SynthSounds>,
+) {
+    let layers = [
+        (
+            &loading.calm,
+            &synth.calm_music,
+            CALM_TRACK,
+            MusicLayer::Calm,
+            "Calm music",
+        ),
+        (
+            &loading.combat,
+            &synth.combat_music,
+            COMBAT_TRACK,
+            MusicLayer::Combat,
+            "Combat music",
+        ),
+    ];
+    let still_loading = layers.iter().any(|(handle, ..)| {
+        !matches!(
+            asset_server.load_state(*handle),
+            LoadState::Loaded | LoadState::Failed(_)
+        )
+    });
+    if still_loading {
+        return;
+    }
+
+    for (handle, synthesized, path, layer, name) in layers {
+        let source = if asset_server.load_state(handle) == LoadState::Loaded {
+            handle.clone()
+        } else {
+            warn!("Playing synthesized music, as {path} could not be loaded");
+            synthesized.clone()
+        };
+        commands.spawn((
+            AudioBundle {
+                source,
+                settings: PlaybackSettings::LOOP.with_volume(Volume::new(0.0)),
+            },
+            layer,
+            Name::new(name),
+        ));
+    }
+    commands.remove_resource::<LoadingMusic>();
+}
+
+// Works out how intense the game is from the number of active AI, the enemy bullets near the
+// player and the player's health, then eases the current intensity towards it.
+pub fn update_music_intensity(
+    time: Res<Time>,
+    ais: Query<&Active, With<Ai>>,
+    players: Query<(&Transform, &Health), With<Player>>,
+    bullets: Query<(&Transform, &Bullet)>,
+    mut intensity: ResMut<MusicIntensity>,
+) {
+    let mut target = 0.0;
+
+    // With no player alive there is nothing to be tense about
+    if let Ok((player_transform, health)) = players.get_single() {
+        // Each active AI adds to the tension, up to three of them
+        let active_ais = ais.iter().filter(|active| active.value).count();
+        target += (active_ais as f32 / 3.0).min(1.0) * 0.4;
+
+        // Enemy bullets flying close to the player
+        let nearby_bullets = bullets
+            .iter()
+            .filter(|(transform, bullet)| {
+                matches!(bullet.from, TurretOf::Ai)
+                    && transform
+                        .translation
+                        .truncate()
+                        .distance(player_transform.translation.truncate())
+                        < DANGER_RADIUS
+            })
+            .count();
+        target += (nearby_bullets as f32 / 3.0).min(1.0) * 0.4;
+
+        // The lower the player's health, the more intense
+        target += (1.0 - health.fraction()).clamp(0.0, 1.0) * 0.2;
+    }
+
+    let follow = (INTENSITY_FOLLOW_SPEED * time.delta_seconds()).min(1.0);
+    intensity.0 += (target - intensity.0) * follow;
+}
+
+// Sets the volume of each layer from the intensity, the volume settings and the pause state
+pub fn crossfade_music(
+    layers: Query<(&AudioSink, &MusicLayer)>,
+    intensity: Res<MusicIntensity>,
+    settings: Res<Settings>,
+    state: Res<State<AppState>>,
+) {
+    let mut volume = settings.master_volume * settings.music_volume;
+    if *state.get() == AppState::Paused {
+        volume *= PAUSE_DUCK;
+    }
+    if MUTE {
+        volume = 0.0;
+    }
+
+    // AudioSinks are only added once the track has loaded, so layers still loading are skipped
+    for (sink, layer) in layers.iter() {
+        match layer {
+            MusicLayer::Calm => sink.set_volume(volume * (1.0 - intensity.0)),
+            MusicLayer::Combat => sink.set_volume(volume * intensity.0),
+        }
+    }
+}
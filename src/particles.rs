@@ -0,0 +1,250 @@
+use bevy::color::Mix;
+use bevy::prelude::*;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+use rand::Rng;
+use std::f32::consts::PI;
+
+// A small CPU particle system. Every particle is a plain sprite that moves,
+// slows down, changes size and colour, and despawns once it is old enough.
+// Effects are bursts of particles described by a `Burst`, so a new effect is
+// just a new set of numbers.
+//
+// Scorch marks are separate: they stay on the floor where a tank died and
+// slowly fade away.
+
+// Just above the floor, below tanks and bullets
+const SCORCH_Z: f32 = -29.0;
+// Above tanks and bullets
+const PARTICLE_Z: f32 = 5.0;
+
+const SCORCH_RADIUS: f32 = 28.0;
+// How long a scorch mark takes to fade away completely
+const SCORCH_FADE_TIME: f32 = 20.0;
+const SCORCH_COLOUR: Color = Color::srgba(0.15, 0.1, 0.08, 0.6);
+
+#[derive(Component)]
+pub struct Particle {
+    velocity: Vec2,
+    // The fraction of its speed the particle loses every second
+    drag: f32,
+    age: f32,
+    lifetime: f32,
+    start_size: f32,
+    end_size: f32,
+    start_colour: LinearRgba,
+    end_colour: LinearRgba,
+}
+
+#[derive(Component)]
+pub struct ScorchMark {
+    age: f32,
+}
+
+// Every scorch mark is the same size, so they all share one mesh
+#[derive(Resource)]
+pub struct ScorchMesh(Mesh2dHandle);
+
+pub fn create_scorch_mesh(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    commands.insert_resource(ScorchMesh(Mesh2dHandle(meshes.add(Circle {
+        radius: SCORCH_RADIUS,
+    }))));
+}
+
+// A description of a burst of particles. Ranges are (min, max), picked at
+// random per particle.
+pub struct Burst {
+    pub count: (u32, u32),
+    pub speed: (f32, f32),
+    // How far either side of the burst's direction particles can go, in
+    // radians. PI sends them every way.
+    pub spread: f32,
+    pub drag: f32,
+    pub lifetime: (f32, f32),
+    pub start_size: (f32, f32),
+    pub end_size: f32,
+    pub start_colour: Color,
+    pub end_colour: Color,
+}
+
+// Grey smoke puffing out of the barrel
+pub const MUZZLE_SMOKE: Burst = Burst {
+    count: (3, 5),
+    speed: (20.0, 70.0),
+    spread: 0.5,
+    drag: 3.0,
+    lifetime: (0.3, 0.6),
+    start_size: (4.0, 7.0),
+    end_size: 14.0,
+    start_colour: Color::srgba(0.85, 0.85, 0.85, 0.6),
+    end_colour: Color::srgba(0.6, 0.6, 0.6, 0.0),
+};
+
+// Bright, quick sparks where a bullet hits a tank
+pub const SPARKS: Burst = Burst {
+    count: (6, 10),
+    speed: (120.0, 260.0),
+    spread: 0.9,
+    drag: 6.0,
+    lifetime: (0.15, 0.3),
+    start_size: (2.0, 3.5),
+    end_size: 1.0,
+    start_colour: Color::srgb(1.0, 0.95, 0.6),
+    end_colour: Color::srgba(1.0, 0.45, 0.1, 0.0),
+};
+
+// Sandy dust where a bullet hits a wall
+pub const DUST: Burst = Burst {
+    count: (5, 8),
+    speed: (30.0, 90.0),
+    spread: 1.2,
+    drag: 4.0,
+    lifetime: (0.3, 0.6),
+    start_size: (3.0, 5.0),
+    end_size: 10.0,
+    start_colour: Color::srgba(0.8, 0.68, 0.52, 0.8),
+    end_colour: Color::srgba(0.7, 0.55, 0.41, 0.0),
+};
+
+// The fireball when a tank is destroyed
+pub const EXPLOSION_FIRE: Burst = Burst {
+    count: (14, 20),
+    speed: (40.0, 160.0),
+    spread: PI,
+    drag: 4.0,
+    lifetime: (0.35, 0.7),
+    start_size: (8.0, 14.0),
+    end_size: 22.0,
+    start_colour: Color::srgb(1.0, 0.8, 0.3),
+    end_colour: Color::srgba(0.3, 0.25, 0.25, 0.0),
+};
+
+// Chunks of tank flying off when it is destroyed
+pub const DEBRIS: Burst = Burst {
+    count: (8, 12),
+    speed: (150.0, 320.0),
+    spread: PI,
+    drag: 3.0,
+    lifetime: (0.5, 0.9),
+    start_size: (3.0, 6.0),
+    end_size: 2.0,
+    start_colour: Color::srgb(0.25, 0.22, 0.2),
+    end_colour: Color::srgba(0.25, 0.22, 0.2, 0.0),
+};
+
+// Spawns a burst of particles at `position`, aimed in `direction`
+pub fn spawn_burst(
+    commands: &mut Commands,
+    burst: &Burst,
+    position: Vec2,
+    direction: Vec2,
+) {
+    let mut rng = rand::thread_rng();
+    let base_angle = direction.to_angle();
+    for _ in 0..rng.gen_range(burst.count.0..=burst.count.1) {
+        let angle = base_angle + rng.gen_range(-burst.spread..=burst.spread);
+        let speed = rng.gen_range(burst.speed.0..=burst.speed.1);
+        let size = rng.gen_range(burst.start_size.0..=burst.start_size.1);
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: burst.start_colour,
+                    custom_size: Some(Vec2::splat(size)),
+                    ..default()
+                },
+                transform: Transform {
+                    translation: position.extend(PARTICLE_Z),
+                    rotation: Quat::from_rotation_z(angle),
+                    ..default()
+                },
+                ..default()
+            },
+            Particle {
+                velocity: Vec2::from_angle(angle) * speed,
+                drag: burst.drag,
+                age: 0.0,
+                lifetime: rng.gen_range(burst.lifetime.0..=burst.lifetime.1),
+                start_size: size,
+                end_size: burst.end_size,
+                start_colour: burst.start_colour.into(),
+                end_colour: burst.end_colour.into(),
+            },
+        ));
+    }
+}
+
+// A fireball, flying debris and a scorch mark left on the floor
+pub fn spawn_tank_explosion(
+    commands: &mut Commands,
+    scorch_mesh: &ScorchMesh,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    position: Vec2,
+) {
+    spawn_burst(commands, &EXPLOSION_FIRE, position, Vec2::X);
+    spawn_burst(commands, &DEBRIS, position, Vec2::X);
+
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: scorch_mesh.0.clone(),
+            // Each mark fades on its own, so it needs its own material
+            material: materials.add(SCORCH_COLOUR),
+            transform: Transform::from_translation(position.extend(SCORCH_Z)),
+            ..default()
+        },
+        ScorchMark { age: 0.0 },
+        Name::new("Scorch mark"),
+    ));
+}
+
+pub fn update_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut particles: Query<(Entity, &mut Particle, &mut Transform, &mut Sprite)>,
+) {
+    let delta = time.delta_seconds();
+    for (entity, mut particle, mut transform, mut sprite) in
+        particles.iter_mut()
+    {
+        particle.age += delta;
+        if particle.age >= particle.lifetime {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let velocity =
+            particle.velocity * (1.0 - particle.drag * delta).max(0.0);
+        particle.velocity = velocity;
+        transform.translation += (velocity * delta).extend(0.0);
+
+        let progress = particle.age / particle.lifetime;
+        let size = particle.start_size
+            + (particle.end_size - particle.start_size) * progress;
+        sprite.custom_size = Some(Vec2::splat(size));
+        sprite.color = particle
+            .start_colour
+            .mix(&particle.end_colour, progress)
+            .into();
+    }
+}
+
+pub fn update_scorch_marks(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut marks: Query<(Entity, &mut ScorchMark, &Handle<ColorMaterial>)>,
+) {
+    for (entity, mut mark, material) in marks.iter_mut() {
+        mark.age += time.delta_seconds();
+        if mark.age >= SCORCH_FADE_TIME {
+            commands.entity(entity).despawn();
+            materials.remove(material);
+            continue;
+        }
+        if let Some(material) = materials.get_mut(material) {
+            let fade = 1.0 - mark.age / SCORCH_FADE_TIME;
+            material.color =
+                SCORCH_COLOUR.with_alpha(SCORCH_COLOUR.alpha() * fade);
+        }
+    }
+}
@@ -0,0 +1,570 @@
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::arena::Arena;
+use crate::healthbars::{HEALTHBAR_HEIGHT, HEALTHBAR_WIDTH};
+use crate::progress_bars::BarSourced;
+use crate::settings::Settings;
+use crate::sound::play_pickup;
+use crate::spatial::TankGrid;
+use crate::tanks::{Magazine, Tank, TANK_SIZE};
+use crate::utils::Health;
+use crate::MUTE;
+
+// Things that tanks can drive over to pick up.
+
+// Repair kits appear at random spots in the arena every so often, and heal the
+// first damaged tank to drive over them
+
+pub const REPAIR_KIT_SIZE: f32 = 14.0;
+// How much health a repair kit restores
+pub const REPAIR_AMOUNT: f32 = 2.0;
+// Seconds between repair kits appearing
+pub const REPAIR_KIT_INTERVAL: f32 = 12.0;
+// No more repair kits spawn while this many are lying around
+pub const MAX_REPAIR_KITS: usize = 2;
+
+#[derive(Component)]
+pub struct RepairKit;
+
+// Picks a random point in the arena for a pickup of the given size, far enough
+// from the walls that tanks can reach it
+fn random_arena_point(arena: &Arena, size: f32) -> Vec2 {
+    let room = arena.half_size() - Vec2::splat(TANK_SIZE + size);
+    let mut rng = rand::thread_rng();
+    Vec2::new(
+        rng.gen_range(-room.x..room.x),
+        rng.gen_range(-room.y..room.y),
+    )
+}
+
+#[derive(Resource)]
+pub struct RepairKitTimer(pub Timer);
+
+impl Default for RepairKitTimer {
+    fn default() -> RepairKitTimer {
+        RepairKitTimer(Timer::from_seconds(
+            REPAIR_KIT_INTERVAL,
+            TimerMode::Repeating,
+        ))
+    }
+}
+
+pub fn spawn_repair_kits(
+    mut commands: Commands,
+    time: Res<Time>,
+    arena: Res<Arena>,
+    mut timer: ResMut<RepairKitTimer>,
+    kits: Query<(), With<RepairKit>>,
+) {
+    if !timer.0.tick(time.delta()).just_finished()
+        || kits.iter().count() >= MAX_REPAIR_KITS
+    {
+        return;
+    }
+
+    let Vec2 { x, y } = random_arena_point(&arena, REPAIR_KIT_SIZE);
+
+    // A white box with a green cross on it
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::srgb(0.95, 0.95, 0.95),
+                    custom_size: Some(Vec2::splat(REPAIR_KIT_SIZE * 2.0)),
+                    ..default()
+                },
+                transform: Transform::from_xyz(x, y, -10.0),
+                ..default()
+            },
+            RepairKit,
+            Name::new("Repair kit"),
+        ))
+        .with_children(|parent| {
+            for size in [Vec2::new(0.8, 0.3), Vec2::new(0.3, 0.8)] {
+                parent.spawn(SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::srgb(0.2, 0.75, 0.35),
+                        custom_size: Some(size * REPAIR_KIT_SIZE * 2.0),
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(0.0, 0.0, 0.1),
+                    ..default()
+                });
+            }
+        });
+}
+
+// Tanks at full health drive straight over repair kits, so they are left for
+// someone who needs them
+pub fn collect_repair_kits(
+    mut commands: Commands,
+    kits: Query<(Entity, &Transform), With<RepairKit>>,
+    mut tanks: Query<
+        (&Transform, &mut Health),
+        (With<Tank>, Without<RepairKit>),
+    >,
+    grid: Res<TankGrid>,
+) {
+    for (kit_entity, kit_transform) in kits.iter() {
+        let kit_position = kit_transform.translation.truncate();
+        for entry in grid.nearby(kit_position, REPAIR_KIT_SIZE) {
+            let Ok((tank_transform, mut health)) = tanks.get_mut(entry.entity)
+            else {
+                continue;
+            };
+            let distance =
+                tank_transform.translation.truncate().distance(kit_position);
+            if distance < entry.radius + REPAIR_KIT_SIZE
+                && health.current < health.max
+            {
+                health.heal(REPAIR_AMOUNT);
+                commands.entity(kit_entity).despawn_recursive();
+                if !MUTE {
+                    commands.add(|world: &mut World| {
+                        world.run_system_once(play_pickup);
+                    })
+                }
+                break;
+            }
+        }
+    }
+}
+
+// Ammo crates top up the reserve ammunition of tanks with limited ammo. Like
+// repair kits, they appear at random spots and are left alone by tanks that
+// don't need them.
+
+pub const AMMO_CRATE_SIZE: f32 = 12.0;
+// How much reserve ammo a crate gives
+pub const AMMO_CRATE_AMOUNT: u32 = 12;
+pub const AMMO_CRATE_INTERVAL: f32 = 10.0;
+pub const MAX_AMMO_CRATES: usize = 2;
+
+#[derive(Component)]
+pub struct AmmoCrate;
+
+#[derive(Resource)]
+pub struct AmmoCrateTimer(pub Timer);
+
+impl Default for AmmoCrateTimer {
+    fn default() -> AmmoCrateTimer {
+        AmmoCrateTimer(Timer::from_seconds(
+            AMMO_CRATE_INTERVAL,
+            TimerMode::Repeating,
+        ))
+    }
+}
+
+pub fn spawn_ammo_crates(
+    mut commands: Commands,
+    time: Res<Time>,
+    arena: Res<Arena>,
+    mut timer: ResMut<AmmoCrateTimer>,
+    crates: Query<(), With<AmmoCrate>>,
+    settings: Res<Settings>,
+) {
+    // Nobody needs ammo when it is unlimited
+    if !settings.limited_ammo {
+        return;
+    }
+    if !timer.0.tick(time.delta()).just_finished()
+        || crates.iter().count() >= MAX_AMMO_CRATES
+    {
+        return;
+    }
+
+    let Vec2 { x, y } = random_arena_point(&arena, AMMO_CRATE_SIZE);
+
+    // An olive box with two yellow stripes on it
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::srgb(0.35, 0.38, 0.2),
+                    custom_size: Some(Vec2::splat(AMMO_CRATE_SIZE * 2.0)),
+                    ..default()
+                },
+                transform: Transform::from_xyz(x, y, -10.0),
+                ..default()
+            },
+            AmmoCrate,
+            Name::new("Ammo crate"),
+        ))
+        .with_children(|parent| {
+            for stripe_y in [-0.25, 0.25] {
+                parent.spawn(SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::srgb(0.95, 0.8, 0.25),
+                        custom_size: Some(
+                            Vec2::new(1.6, 0.2) * AMMO_CRATE_SIZE,
+                        ),
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(
+                        0.0,
+                        stripe_y * AMMO_CRATE_SIZE * 2.0,
+                        0.1,
+                    ),
+                    ..default()
+                });
+            }
+        });
+}
+
+// Only tanks with a limited reserve pick up ammo crates
+pub fn collect_ammo_crates(
+    mut commands: Commands,
+    crates: Query<(Entity, &Transform), With<AmmoCrate>>,
+    mut tanks: Query<
+        (&Transform, &mut Magazine),
+        (With<Tank>, Without<AmmoCrate>),
+    >,
+    grid: Res<TankGrid>,
+) {
+    for (crate_entity, crate_transform) in crates.iter() {
+        let crate_position = crate_transform.translation.truncate();
+        for entry in grid.nearby(crate_position, AMMO_CRATE_SIZE) {
+            let Ok((tank_transform, mut magazine)) =
+                tanks.get_mut(entry.entity)
+            else {
+                continue;
+            };
+            let distance = tank_transform
+                .translation
+                .truncate()
+                .distance(crate_position);
+            let Some(reserve) = magazine.reserve.as_mut() else {
+                continue;
+            };
+            if distance < entry.radius + AMMO_CRATE_SIZE {
+                *reserve += AMMO_CRATE_AMOUNT;
+                commands.entity(crate_entity).despawn_recursive();
+                if !MUTE {
+                    commands.add(|world: &mut World| {
+                        world.run_system_once(play_pickup);
+                    })
+                }
+                break;
+            }
+        }
+    }
+}
+
+// Power-ups grant a timed effect to whichever tank drives over them. They
+// appear at a set of spawn points rather than anywhere in the arena, so that
+// players can learn where to fight over them.
+
+pub const POWER_UP_SIZE: f32 = 12.0;
+// Seconds between power-ups appearing
+pub const POWER_UP_INTERVAL: f32 = 15.0;
+pub const MAX_POWER_UPS: usize = 2;
+
+pub const SPEED_BOOST_MULTIPLIER: f32 = 1.5;
+// The fire cooldown is multiplied by this while rapid fire is active
+pub const RAPID_FIRE_MULTIPLIER: f32 = 0.5;
+// The angle between the bullets of a triple shot, in radians
+pub const TRIPLE_SHOT_SPREAD: f32 = 0.2;
+// How many times a bullet bounces off the walls while bouncing bullets is
+// active
+pub const BULLET_BOUNCES: u8 = 2;
+
+const EFFECT_ICON_SIZE: f32 = 9.0;
+const EFFECT_ICON_GAP: f32 = 3.0;
+// Icons start blinking when their effect has less than this many seconds left
+const EFFECT_ICON_BLINK_TIME: f32 = 2.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PowerUp {
+    SpeedBoost,
+    RapidFire,
+    Shield,
+    TripleShot,
+    BouncingBullets,
+}
+
+impl PowerUp {
+    pub const ALL: [PowerUp; 5] = [
+        PowerUp::SpeedBoost,
+        PowerUp::RapidFire,
+        PowerUp::Shield,
+        PowerUp::TripleShot,
+        PowerUp::BouncingBullets,
+    ];
+
+    // How long the effect lasts once picked up, in seconds
+    pub fn duration(&self) -> f32 {
+        match self {
+            PowerUp::SpeedBoost => 8.0,
+            PowerUp::RapidFire => 6.0,
+            PowerUp::Shield => 5.0,
+            PowerUp::TripleShot => 8.0,
+            PowerUp::BouncingBullets => 10.0,
+        }
+    }
+
+    // Used for both the pickup and the icon above the tank
+    pub fn colour(&self) -> Color {
+        match self {
+            PowerUp::SpeedBoost => Color::srgb(0.3, 0.85, 0.95),
+            PowerUp::RapidFire => Color::srgb(0.95, 0.35, 0.25),
+            PowerUp::Shield => Color::srgb(0.35, 0.45, 0.95),
+            PowerUp::TripleShot => Color::srgb(0.7, 0.35, 0.9),
+            PowerUp::BouncingBullets => Color::srgb(0.45, 0.85, 0.3),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct PowerUpPickup(pub PowerUp);
+
+// Where and how often power-ups spawn. Replace this resource to change the
+// spawn points.
+#[derive(Resource)]
+pub struct PowerUpSpawner {
+    pub points: Vec<Vec2>,
+    pub timer: Timer,
+}
+
+impl Default for PowerUpSpawner {
+    // The centre of the arena, and halfway to each of its corners
+    fn default() -> PowerUpSpawner {
+        let quarter = Arena::default().size / 4.0;
+        PowerUpSpawner {
+            points: vec![
+                Vec2::new(0.0, 0.0),
+                Vec2::new(-quarter.x, quarter.y),
+                Vec2::new(quarter.x, quarter.y),
+                Vec2::new(-quarter.x, -quarter.y),
+                Vec2::new(quarter.x, -quarter.y),
+            ],
+            timer: Timer::from_seconds(POWER_UP_INTERVAL, TimerMode::Repeating),
+        }
+    }
+}
+
+// The power-ups a tank currently has, with the time each has left
+#[derive(Component, Default)]
+pub struct ActiveEffects(pub Vec<(PowerUp, f32)>);
+
+impl ActiveEffects {
+    pub fn has(&self, power_up: PowerUp) -> bool {
+        self.0.iter().any(|(effect, _)| *effect == power_up)
+    }
+
+    // Picking up an effect that is already active resets its duration
+    pub fn add(&mut self, power_up: PowerUp) {
+        self.0.retain(|(effect, _)| *effect != power_up);
+        self.0.push((power_up, power_up.duration()));
+    }
+
+    pub fn speed_multiplier(&self) -> f32 {
+        if self.has(PowerUp::SpeedBoost) {
+            SPEED_BOOST_MULTIPLIER
+        } else {
+            1.0
+        }
+    }
+
+    pub fn cooldown_multiplier(&self) -> f32 {
+        if self.has(PowerUp::RapidFire) {
+            RAPID_FIRE_MULTIPLIER
+        } else {
+            1.0
+        }
+    }
+}
+
+// Marks the icon above a tank that shows one of its active effects
+#[derive(Component)]
+pub struct EffectIcon(pub PowerUp);
+
+pub fn spawn_power_ups(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut spawner: ResMut<PowerUpSpawner>,
+    power_ups: Query<&Transform, With<PowerUpPickup>>,
+) {
+    if !spawner.timer.tick(time.delta()).just_finished()
+        || power_ups.iter().count() >= MAX_POWER_UPS
+    {
+        return;
+    }
+
+    // Only spawn on points that don't already have a power-up on them
+    let free_points: Vec<Vec2> = spawner
+        .points
+        .iter()
+        .copied()
+        .filter(|point| {
+            power_ups.iter().all(|transform| {
+                transform.translation.truncate().distance(*point)
+                    > POWER_UP_SIZE
+            })
+        })
+        .collect();
+    if free_points.is_empty() {
+        return;
+    }
+    let mut rng = rand::thread_rng();
+    let point = free_points[rng.gen_range(0..free_points.len())];
+    let power_up = PowerUp::ALL[rng.gen_range(0..PowerUp::ALL.len())];
+
+    // A diamond in the power-up's colour with a white outline
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::srgb(0.95, 0.95, 0.95),
+                    custom_size: Some(Vec2::splat(POWER_UP_SIZE * 1.6)),
+                    ..default()
+                },
+                transform: Transform::from_xyz(point.x, point.y, -10.0)
+                    .with_rotation(Quat::from_rotation_z(
+                        std::f32::consts::FRAC_PI_4,
+                    )),
+                ..default()
+            },
+            PowerUpPickup(power_up),
+            Name::new("Power-up"),
+        ))
+        .with_children(|parent| {
+            parent.spawn(SpriteBundle {
+                sprite: Sprite {
+                    color: power_up.colour(),
+                    custom_size: Some(Vec2::splat(POWER_UP_SIZE * 1.2)),
+                    ..default()
+                },
+                transform: Transform::from_xyz(0.0, 0.0, 0.1),
+                ..default()
+            });
+        });
+}
+
+pub fn collect_power_ups(
+    mut commands: Commands,
+    power_ups: Query<(Entity, &Transform, &PowerUpPickup)>,
+    mut tanks: Query<
+        (&Transform, &mut ActiveEffects),
+        (With<Tank>, Without<PowerUpPickup>),
+    >,
+    grid: Res<TankGrid>,
+) {
+    for (pickup_entity, pickup_transform, pickup) in power_ups.iter() {
+        let pickup_position = pickup_transform.translation.truncate();
+        for entry in grid.nearby(pickup_position, POWER_UP_SIZE) {
+            let Ok((tank_transform, mut effects)) = tanks.get_mut(entry.entity)
+            else {
+                continue;
+            };
+            let distance = tank_transform
+                .translation
+                .truncate()
+                .distance(pickup_position);
+            if distance < entry.radius + POWER_UP_SIZE {
+                effects.add(pickup.0);
+                commands.entity(pickup_entity).despawn_recursive();
+                if !MUTE {
+                    commands.add(|world: &mut World| {
+                        world.run_system_once(play_pickup);
+                    })
+                }
+                break;
+            }
+        }
+    }
+}
+
+// Counts down active effects and removes the ones that have run out
+pub fn update_effects(time: Res<Time>, mut tanks: Query<&mut ActiveEffects>) {
+    for mut effects in tanks.iter_mut() {
+        for (_, time_left) in effects.0.iter_mut() {
+            *time_left -= time.delta_seconds();
+        }
+        effects.0.retain(|(_, time_left)| *time_left > 0.0);
+    }
+}
+
+// Where the icon for the effect at `index` in a tank's effect list goes,
+// relative to the healthbar. The icons sit in a row just to the right of it.
+fn effect_icon_translation(index: usize) -> Vec3 {
+    let x = HEALTHBAR_WIDTH / 2.0
+        + HEALTHBAR_HEIGHT
+        + EFFECT_ICON_GAP * 2.0
+        + index as f32 * (EFFECT_ICON_SIZE + EFFECT_ICON_GAP);
+    Vec3::new(x, 0.0, 0.5)
+}
+
+// Keeps a row of icons next to each tank's healthbar matching its active
+// effects. The icons are children of the healthbar, so they stay next to it
+// when it is kept on screen.
+pub fn update_effect_icons(
+    mut commands: Commands,
+    time: Res<Time>,
+    tanks: Query<(&ActiveEffects, &Children)>,
+    healthbars: Query<Option<&Children>, With<BarSourced<Health>>>,
+    mut icons: Query<(&EffectIcon, &mut Transform, &mut Sprite)>,
+) {
+    // Blinking icons are shown for half of each blink
+    let blink_on = (time.elapsed_seconds() * 8.0).sin() > 0.0;
+
+    for (effects, tank_children) in tanks.iter() {
+        let Some((healthbar, children)) = tank_children
+            .iter()
+            .find_map(|&child| Some((child, healthbars.get(child).ok()?)))
+        else {
+            continue;
+        };
+        let mut shown = Vec::new();
+
+        // Update the icons the tank already has, removing those whose effect
+        // has expired
+        for &child in children.into_iter().flatten() {
+            let Ok((icon, mut transform, mut sprite)) = icons.get_mut(child)
+            else {
+                continue;
+            };
+            let Some(index) =
+                effects.0.iter().position(|(effect, _)| *effect == icon.0)
+            else {
+                commands.entity(child).despawn_recursive();
+                continue;
+            };
+            // Blink the icon when the effect is about to run out
+            let time_left = effects.0[index].1;
+            transform.translation = effect_icon_translation(index);
+            sprite.color = if time_left > EFFECT_ICON_BLINK_TIME || blink_on {
+                icon.0.colour()
+            } else {
+                Color::NONE
+            };
+            shown.push(icon.0);
+        }
+
+        // Add icons for newly picked up effects
+        for (index, (power_up, _)) in effects.0.iter().enumerate() {
+            if shown.contains(power_up) {
+                continue;
+            }
+            let icon = commands
+                .spawn((
+                    SpriteBundle {
+                        sprite: Sprite {
+                            color: power_up.colour(),
+                            custom_size: Some(Vec2::splat(EFFECT_ICON_SIZE)),
+                            ..default()
+                        },
+                        transform: Transform::from_translation(
+                            effect_icon_translation(index),
+                        ),
+                        ..default()
+                    },
+                    EffectIcon(*power_up),
+                    Name::new("Effect icon"),
+                ))
+                .id();
+            commands.entity(healthbar).add_child(icon);
+        }
+    }
+}
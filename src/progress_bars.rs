@@ -0,0 +1,310 @@
+use bevy::color::Mix;
+use bevy::prelude::*;
+use bevy::sprite::Mesh2dHandle;
+use std::marker::PhantomData;
+
+use crate::arena::Arena;
+use crate::camera::{visible_area, FollowCamera};
+
+// A rounded bar that floats next to an entity in the world, such as a healthbar. Each bar is made of
+// a root entity holding its settings, with two children: a border (the bar's background) and the
+// fill on top of it. Both have a circle on each end to round them off.
+//
+// The root is a child of the entity it belongs to:
+//
+//     Tank
+//     └── ProgressBar
+//         ├── BarBorder
+//         │   ├── BarSide(Left)
+//         │   └── BarSide(Right)
+//         └── BarFill
+//             ├── BarSide(Left)
+//             └── BarSide(Right)
+//
+// A bar shows `ProgressBar::value`. Either write to it directly, or give the bar a `BarSourced<T>`
+// so that it is filled in from the parent's `T` component by the `read_bar_source::<T>` system.
+
+// Anything a bar can show
+pub trait BarSource: Component {
+    // How full the bar should be, from 0.0 to 1.0
+    fn bar_fraction(&self) -> f32;
+}
+
+// The colours a bar goes through from empty to full
+#[derive(Clone, Copy)]
+pub struct Gradient {
+    pub empty: Hsla,
+    pub full: Hsla,
+}
+
+impl Gradient {
+    // A gradient that is the same colour the whole way through
+    pub fn solid(colour: Hsla) -> Gradient {
+        Gradient {
+            empty: colour,
+            full: colour,
+        }
+    }
+
+    pub fn at(&self, fraction: f32) -> Color {
+        self.empty.mix(&self.full, fraction).into()
+    }
+}
+
+#[derive(Clone)]
+pub struct BarStyle {
+    pub width: f32,
+    // The height of the fill. The border is `border_thickness` bigger on each side.
+    pub height: f32,
+    pub border_thickness: f32,
+    // Where the bar sits relative to its parent
+    pub offset: Vec2,
+    pub fill: Gradient,
+    pub border: Gradient,
+    // Stops the bar going off the edges of the screen when its parent is near them
+    pub clamp_to_screen: bool,
+    // Extra room to leave above the bar when it is pushed against the top of the screen, so that
+    // bars stacked on top of each other don't end up overlapping
+    pub top_margin: f32,
+    // If set, the bar fills up at this speed (fraction per second) rather than jumping straight to
+    // the new value. Decreases are always shown instantly.
+    pub fill_speed: Option<f32>,
+}
+
+impl BarStyle {
+    pub fn border_height(&self) -> f32 {
+        self.height + self.border_thickness * 2.0
+    }
+}
+
+#[derive(Component)]
+pub struct ProgressBar {
+    pub style: BarStyle,
+    // The value the bar should show, from 0.0 to 1.0
+    pub value: f32,
+    // The value the bar is currently showing, which lags behind `value` when fill_speed is set
+    displayed: f32,
+}
+
+#[derive(Component)]
+pub struct BarFill;
+
+#[derive(Component)]
+pub struct BarBorder;
+
+pub enum Side {
+    Right,
+    Left,
+}
+
+#[derive(Component)]
+pub struct BarSide(pub Side);
+
+// Makes a bar show the parent's `T`
+#[derive(Component)]
+pub struct BarSourced<T: BarSource>(PhantomData<T>);
+
+impl<T: BarSource> BarSourced<T> {
+    pub fn new() -> BarSourced<T> {
+        BarSourced(PhantomData)
+    }
+}
+
+// Spawns a fully wired bar as a child of `parent`. `extra` is added to the bar's root, which is
+// usually a BarSourced to choose what the bar shows, or () to set the value manually.
+pub fn spawn_progress_bar(
+    parent: &mut ChildBuilder,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    style: BarStyle,
+    extra: impl Bundle,
+) -> Entity {
+    let border_height = style.border_height();
+    let width = style.width;
+    let height = style.height;
+    let (fill_colour, border_colour) = (style.fill.at(1.0), style.border.at(1.0));
+
+    parent
+        .spawn((
+            SpatialBundle::from_transform(Transform::from_translation(style.offset.extend(0.5))),
+            ProgressBar {
+                style,
+                value: 1.0,
+                displayed: 1.0,
+            },
+            Name::new("Progress bar"),
+            extra,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    SpriteBundle {
+                        sprite: Sprite {
+                            color: border_colour,
+                            ..default()
+                        },
+                        transform: Transform::from_scale(Vec3::new(width, border_height, 0.0)),
+                        ..default()
+                    },
+                    BarBorder,
+                ))
+                .with_children(|parent| spawn_bar_sides(parent, meshes, materials));
+            parent
+                .spawn((
+                    SpriteBundle {
+                        sprite: Sprite {
+                            color: fill_colour,
+                            ..default()
+                        },
+                        transform: Transform {
+                            scale: Vec3::new(width, height, 0.0),
+                            translation: Vec3::new(0.0, 0.0, 0.5),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    BarFill,
+                ))
+                .with_children(|parent| spawn_bar_sides(parent, meshes, materials));
+        })
+        .id()
+}
+
+fn spawn_bar_sides(
+    parent: &mut ChildBuilder,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+) {
+    for side in [Side::Left, Side::Right] {
+        parent.spawn((
+            bevy::sprite::MaterialMesh2dBundle {
+                mesh: Mesh2dHandle(meshes.add(Circle { radius: 0.5 })),
+                material: materials.add(Color::NONE),
+                transform: Transform::from_xyz(0.0, 0.0, 0.1),
+                ..default()
+            },
+            BarSide(side),
+        ));
+    }
+}
+
+// Copies the parent's `T` into the value of every bar that is sourced from it
+pub fn read_bar_source<T: BarSource>(
+    sources: Query<&T>,
+    mut bars: Query<(&mut ProgressBar, &Parent), With<BarSourced<T>>>,
+) {
+    for (mut bar, parent) in bars.iter_mut() {
+        if let Ok(source) = sources.get(parent.get()) {
+            bar.value = source.bar_fraction().clamp(0.0, 1.0);
+        }
+    }
+}
+
+// Sizes and colours each bar's fill and border from its value
+pub fn update_progress_bars(
+    time: Res<Time>,
+    mut bars: Query<(&mut ProgressBar, &Children)>,
+    mut fills: Query<
+        (&mut Transform, &mut Sprite, &mut Visibility),
+        (With<BarFill>, Without<BarBorder>),
+    >,
+    mut borders: Query<&mut Sprite, (With<BarBorder>, Without<BarFill>)>,
+) {
+    for (mut bar, children) in bars.iter_mut() {
+        // Gains can be animated by easing towards the new value, while losses snap straight to it
+        bar.displayed = match bar.style.fill_speed {
+            Some(speed) if bar.value > bar.displayed => {
+                bar.displayed
+                    + (bar.value - bar.displayed) * (speed * time.delta_seconds()).min(1.0)
+            }
+            _ => bar.value,
+        };
+        let fraction = bar.displayed;
+
+        for &child in children.iter() {
+            if let Ok((mut transform, mut sprite, mut visibility)) = fills.get_mut(child) {
+                let fill_width = fraction * bar.style.width;
+                transform.scale.x = fill_width;
+                // As the bar empties the fill shrinks in width. To keep it left-aligned rather
+                // than centred, we have to shift it left by half of the width it lost.
+                transform.translation.x = -(bar.style.width - fill_width) / 2.0;
+                sprite.color = bar.style.fill.at(fraction);
+                // An empty fill would make its side circles infinitely wide, so it's hidden instead
+                *visibility = if fill_width > 0.01 {
+                    Visibility::Inherited
+                } else {
+                    Visibility::Hidden
+                };
+            }
+            if let Ok(mut sprite) = borders.get_mut(child) {
+                sprite.color = bar.style.border.at(fraction);
+            }
+        }
+    }
+}
+
+// Update the side-circles of the bar fills and borders
+pub fn update_bar_sides(
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    bars: Query<(&Transform, &Sprite), Without<BarSide>>,
+    mut sides: Query<(&mut Transform, &Handle<ColorMaterial>, &Parent, &BarSide)>,
+) {
+    for (mut transform, material_handle, parent, side) in sides.iter_mut() {
+        // We have the side circle's components
+        if let Ok((parent_transform, parent_sprite)) = bars.get(parent.get()) {
+            // We now have the transform and sprite of the side circle's parent
+            // We make the circle just as wide as it is high
+            transform.scale.x = parent_transform.scale.y / parent_transform.scale.x;
+            // We move the circle to the left or the right of its parent
+            match side.0 {
+                Side::Right => transform.translation.x = 0.5,
+                Side::Left => transform.translation.x = -0.5,
+            }
+            // We update the circle's colour
+            let material = materials.get_mut(material_handle.id()).unwrap();
+            material.color = parent_sprite.color;
+        }
+    }
+}
+
+// Snaps bars to the edge of the screen when their parent gets close enough to it that they would
+// otherwise be cut off. "The screen" is the part of the arena the camera can currently see.
+pub fn keep_bars_on_screen(
+    arena: Res<Arena>,
+    mut bars: Query<(&mut Transform, &GlobalTransform, &ProgressBar), Without<FollowCamera>>,
+    cameras: Query<(&Transform, &OrthographicProjection), With<FollowCamera>>,
+) {
+    let Ok((camera, projection)) = cameras.get_single() else {
+        return;
+    };
+    let view =
+        visible_area(camera, projection).intersect(Rect::from_center_size(Vec2::ZERO, arena.size));
+
+    for (mut transform, global_transform, bar) in bars.iter_mut() {
+        let style = &bar.style;
+        if !style.clamp_to_screen {
+            transform.translation = style.offset.extend(transform.translation.z);
+            continue;
+        }
+
+        // The highest the bar's centre can go is the top of the view, minus half of the bar's
+        // height (transforms in Bevy are centred) and any room left for bars above it
+        let ceiling = view.max.y - style.border_height() / 2.0 - style.top_margin;
+        // The side circles add half of the bar's height to each end
+        let half_width = (style.width + style.border_height()) / 2.0;
+        let (left_edge, right_edge) = (view.min.x + half_width, view.max.x - half_width);
+
+        // Calculate the parents's position by subtracting the relative position from the global position
+        let parent = global_transform.translation().truncate() - transform.translation.truncate();
+        // `(ceiling - parent.y)` calculates the maximum height the bar could go to, relative to
+        // the parent. If the parent + offset is below the top of the view the bar goes there,
+        // otherwise it snaps to the top of the view.
+        transform.translation.y = style.offset.y.min(ceiling - parent.y);
+        // The same for the sides
+        transform.translation.x = style
+            .offset
+            .x
+            .min(right_edge - parent.x)
+            .max(left_edge - parent.x);
+    }
+}
@@ -0,0 +1,303 @@
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+use crate::camera::FollowCamera;
+use crate::pickups::ActiveEffects;
+use crate::progress_bars::*;
+use crate::settings::Settings;
+use crate::sound::play_reload;
+use crate::tanks::{Ai, AttackTimer, Magazine, Player, Weapon};
+use crate::MUTE;
+
+// Reloading, for tanks whose weapon has a Magazine.
+//
+// The player sees when their gun is ready to fire again with a small bar under their tank and
+// (optionally) another under the mouse cursor. Both are hidden while the gun is ready. Their ammo
+// count is shown in the corner of the screen.
+
+// AI reload early, before their magazine is empty, when the player is at least this far away
+const AI_EARLY_RELOAD_DISTANCE: f32 = 300.0;
+
+const RELOAD_BAR_WIDTH: f32 = 30.0;
+const RELOAD_BAR_HEIGHT: f32 = 3.0;
+// How far below the tank's hull the bar sits
+const RELOAD_BAR_GAP: f32 = 8.0;
+
+// Sizes of the cursor indicator, in logical pixels
+const CURSOR_BAR_WIDTH: f32 = 24.0;
+const CURSOR_BAR_HEIGHT: f32 = 4.0;
+const CURSOR_BAR_Y_OFFSET: f32 = 16.0;
+
+#[derive(Component)]
+pub struct AmmoText;
+
+// The progress bar under the player's tank
+#[derive(Component)]
+pub struct ReloadBar;
+
+// The UI bar that follows the cursor, and the fill inside it
+#[derive(Component)]
+pub struct CursorReloadIndicator;
+#[derive(Component)]
+pub struct CursorReloadFill;
+
+// How ready a tank is to fire, from 0.0 to 1.0. While reloading this is the reload's progress.
+pub fn fire_readiness(
+    attack_timer: &AttackTimer,
+    weapon: &Weapon,
+    effects: &ActiveEffects,
+    magazine: Option<&Magazine>,
+) -> f32 {
+    match magazine.map(|magazine| (magazine.reload_progress(), magazine.loaded)) {
+        Some((Some(progress), _)) => progress,
+        Some((None, 0)) => 0.0,
+        _ => weapon.readiness(attack_timer, effects),
+    }
+}
+
+// Advances reloads, and starts one automatically when a magazine runs dry
+pub fn update_magazines(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut magazines: Query<&mut Magazine>,
+) {
+    for mut magazine in magazines.iter_mut() {
+        if magazine.loaded == 0 && magazine.start_reload() && !MUTE {
+            commands.add(|world: &mut World| {
+                world.run_system_once(play_reload);
+            })
+        }
+        if let Some(elapsed) = magazine.reloading.as_mut() {
+            *elapsed += time.delta_seconds();
+            if *elapsed >= magazine.reload_time {
+                magazine.finish_reload();
+            }
+        }
+    }
+}
+
+// Key presses are seen every frame, but reloading is part of the fixed-tick simulation, so a press
+// is remembered until the next tick handles it
+#[derive(Resource, Default)]
+pub struct ReloadPressed(bool);
+
+pub fn buffer_reload_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut pressed: ResMut<ReloadPressed>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyR) {
+        pressed.0 = true;
+    }
+}
+
+// Lets the player reload before their magazine is empty
+pub fn reload_input(
+    mut commands: Commands,
+    mut pressed: ResMut<ReloadPressed>,
+    mut magazines: Query<&mut Magazine, With<Player>>,
+) {
+    if !std::mem::take(&mut pressed.0) {
+        return;
+    }
+    for mut magazine in magazines.iter_mut() {
+        if magazine.start_reload() && !MUTE {
+            commands.add(|world: &mut World| {
+                world.run_system_once(play_reload);
+            })
+        }
+    }
+}
+
+// AI top up a half-empty magazine while the player is far away (or dead), rather than waiting to
+// run out in the middle of a fight
+pub fn ai_reload(
+    mut commands: Commands,
+    players: Query<&Transform, With<Player>>,
+    mut ais: Query<(&Transform, &mut Magazine), (With<Ai>, Without<Player>)>,
+) {
+    for (transform, mut magazine) in ais.iter_mut() {
+        let player_far = players.iter().all(|player| {
+            player
+                .translation
+                .truncate()
+                .distance(transform.translation.truncate())
+                > AI_EARLY_RELOAD_DISTANCE
+        });
+        if player_far && magazine.loaded <= magazine.size / 2 && magazine.start_reload() && !MUTE {
+            commands.add(|world: &mut World| {
+                world.run_system_once(play_reload);
+            })
+        }
+    }
+}
+
+pub fn spawn_ammo_text(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/PT_Sans/PTSans-Regular.ttf"),
+                font_size: 24.0,
+                color: Color::srgb(0.9, 0.9, 0.9),
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(12.0),
+            bottom: Val::Px(8.0),
+            ..default()
+        }),
+        AmmoText,
+        Name::new("Ammo text"),
+    ));
+}
+
+pub fn update_ammo_text(
+    players: Query<&Magazine, With<Player>>,
+    mut texts: Query<&mut Text, With<AmmoText>>,
+) {
+    // The text is empty when there is no player, or the player has unlimited ammo
+    let value = match players.get_single() {
+        Ok(magazine) if magazine.reloading.is_some() => "Reloading...".to_string(),
+        Ok(magazine) => match magazine.reserve {
+            Some(reserve) => format!(
+                "Ammo: {}/{}  ({} spare)",
+                magazine.loaded, magazine.size, reserve
+            ),
+            None => format!("Ammo: {}/{}", magazine.loaded, magazine.size),
+        },
+        Err(_) => String::new(),
+    };
+    for mut text in texts.iter_mut() {
+        text.sections[0].value.clone_from(&value);
+    }
+}
+
+pub fn spawn_reload_bar(
+    parent: &mut ChildBuilder,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    tank_radius: f32,
+) -> Entity {
+    let style = BarStyle {
+        width: RELOAD_BAR_WIDTH,
+        height: RELOAD_BAR_HEIGHT,
+        border_thickness: 1.0,
+        offset: Vec2::new(0.0, -(tank_radius + RELOAD_BAR_GAP)),
+        fill: Gradient {
+            empty: Hsla::hsl(40.0, 0.2, 0.75),
+            full: Hsla::hsl(40.0, 0.9, 0.75),
+        },
+        border: Gradient::solid(Hsla::hsl(0.0, 0.0, 0.15)),
+        clamp_to_screen: false,
+        top_margin: 0.0,
+        fill_speed: None,
+    };
+    spawn_progress_bar(parent, meshes, materials, style, ReloadBar)
+}
+
+pub fn spawn_cursor_reload_indicator(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Px(CURSOR_BAR_WIDTH),
+                    height: Val::Px(CURSOR_BAR_HEIGHT),
+                    display: Display::None,
+                    ..default()
+                },
+                background_color: Color::srgb(0.15, 0.15, 0.15).into(),
+                border_radius: BorderRadius::MAX,
+                ..default()
+            },
+            CursorReloadIndicator,
+            Name::new("Cursor reload indicator"),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        width: Val::Percent(0.0),
+                        height: Val::Percent(100.0),
+                        ..default()
+                    },
+                    background_color: Color::hsl(40.0, 0.9, 0.75).into(),
+                    border_radius: BorderRadius::MAX,
+                    ..default()
+                },
+                CursorReloadFill,
+            ));
+        });
+}
+
+pub fn update_reload_indicators(
+    settings: Res<Settings>,
+    ui_scale: Res<UiScale>,
+    primary_window: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<&Camera, With<FollowCamera>>,
+    players: Query<
+        (
+            &AttackTimer,
+            &Weapon,
+            &ActiveEffects,
+            Option<&Magazine>,
+            &Children,
+        ),
+        With<Player>,
+    >,
+    mut reload_bars: Query<(&mut ProgressBar, &mut Visibility), With<ReloadBar>>,
+    mut cursor_indicator: Query<
+        &mut Style,
+        (With<CursorReloadIndicator>, Without<CursorReloadFill>),
+    >,
+    mut cursor_fill: Query<&mut Style, (With<CursorReloadFill>, Without<CursorReloadIndicator>)>,
+) {
+    // With no player there is nothing to reload
+    let readiness =
+        players
+            .get_single()
+            .ok()
+            .map(|(attack_timer, weapon, effects, magazine, children)| {
+                let readiness = fire_readiness(attack_timer, weapon, effects, magazine);
+                for &child in children.iter() {
+                    if let Ok((mut bar, mut visibility)) = reload_bars.get_mut(child) {
+                        bar.value = readiness;
+                        *visibility = if readiness < 1.0 {
+                            Visibility::Inherited
+                        } else {
+                            Visibility::Hidden
+                        };
+                    }
+                }
+                readiness
+            });
+
+    let Ok(mut indicator_style) = cursor_indicator.get_single_mut() else {
+        return;
+    };
+    // The UI is laid out inside the camera's viewport and scaled along with the game (see
+    // display.rs), so the cursor's window position has to be converted to match
+    let viewport_origin = cameras
+        .get_single()
+        .ok()
+        .and_then(|camera| camera.logical_viewport_rect())
+        .map_or(Vec2::ZERO, |viewport| viewport.min);
+    let cursor = primary_window
+        .get_single()
+        .ok()
+        .and_then(|window| window.cursor_position())
+        .map(|cursor| (cursor - viewport_origin) / ui_scale.0);
+    match (readiness, cursor) {
+        (Some(readiness), Some(cursor)) if readiness < 1.0 && settings.cursor_reload_indicator => {
+            indicator_style.display = Display::Flex;
+            indicator_style.left = Val::Px(cursor.x - CURSOR_BAR_WIDTH / 2.0);
+            indicator_style.top = Val::Px(cursor.y + CURSOR_BAR_Y_OFFSET);
+            for mut fill_style in cursor_fill.iter_mut() {
+                fill_style.width = Val::Percent(readiness * 100.0);
+            }
+        }
+        _ => indicator_style.display = Display::None,
+    }
+}
@@ -0,0 +1,99 @@
+use bevy::prelude::*;
+
+use crate::driving::ControlScheme;
+
+// Player-adjustable settings. Anything that a settings menu should be able to change lives here, so
+// that systems read one resource rather than a scattering of constants.
+#[derive(Resource)]
+pub struct Settings {
+    // Multiplies every sound the game makes
+    pub master_volume: f32,
+    // Multiplies only the background music, on top of the master volume
+    pub music_volume: f32,
+    // Whether a reload bar follows the mouse cursor, as well as the one under the player's tank
+    pub cursor_reload_indicator: bool,
+    // Whether tanks have magazines that need reloading, rather than unlimited fire
+    pub limited_ammo: bool,
+    // How strongly the screen shakes and kicks during combat. 0.0 turns it off.
+    pub screen_shake: f32,
+    // Whether the game briefly freezes when something is killed
+    pub hit_stop: bool,
+    // How the player and the AI steer their tanks (see driving.rs)
+    pub player_controls: ControlScheme,
+    pub ai_controls: ControlScheme,
+    // The tank class the player spawns as, and the ones AI are picked from (see tank_classes.rs)
+    pub player_class: String,
+    pub ai_classes: Vec<String>,
+    // Whether bullets from opposing tanks knock each other out of the air when they touch
+    pub bullet_collisions: bool,
+    pub difficulty: Difficulty,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            master_volume: 1.0,
+            music_volume: 0.6,
+            cursor_reload_indicator: true,
+            limited_ammo: false,
+            screen_shake: 1.0,
+            hit_stop: true,
+            player_controls: ControlScheme::Direct,
+            ai_controls: ControlScheme::Direct,
+            player_class: "medium".to_string(),
+            ai_classes: ["light", "medium", "heavy", "artillery"]
+                .map(String::from)
+                .to_vec(),
+            bullet_collisions: false,
+            difficulty: Difficulty::Normal,
+        }
+    }
+}
+
+// How good the AI are
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+    Veteran,
+}
+
+impl Difficulty {
+    // How fast AI get through their weapon's cooldown, compared to the player
+    pub fn fire_rate(self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.6,
+            Difficulty::Normal | Difficulty::Hard | Difficulty::Veteran => 1.0,
+        }
+    }
+
+    // How close the player's bullets have to get before AI try to shoot them down, on the
+    // difficulties where they do. This only happens when bullet collisions are on.
+    pub fn intercept_range(self) -> Option<f32> {
+        match self {
+            Difficulty::Easy | Difficulty::Normal => None,
+            Difficulty::Hard => Some(250.0),
+            Difficulty::Veteran => Some(400.0),
+        }
+    }
+
+    // The difficulty after this one, wrapping round to the easiest, for the difficulty button
+    pub fn next(self) -> Difficulty {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Veteran,
+            Difficulty::Veteran => Difficulty::Easy,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+            Difficulty::Veteran => "Veteran",
+        }
+    }
+}
@@ -1,6 +1,10 @@
+use bevy::asset::LoadState;
 use bevy::prelude::*;
 
+use crate::synth::SynthSounds;
+
 // Each sound has two parts, a higher and a lower version. Both are played in unison.
+// If the recorded .ogg files can't be loaded, a synthesized version of the sound is played instead.
 
 #[derive(Component)]
 pub struct GunShotSound;
@@ -17,11 +21,95 @@ pub struct WallHitSound;
 #[derive(Component)]
 pub struct WallHitDeepSound;
 
+// These only exist as synthesized sounds
+#[derive(Component)]
+pub struct ShieldHitSound;
+#[derive(Component)]
+pub struct ReloadSound;
+#[derive(Component)]
+pub struct ExplosionSound;
+#[derive(Component)]
+pub struct PickupSound;
+#[derive(Component)]
+pub struct BulletClashSound;
+#[derive(Component)]
+pub struct CountdownSound;
+
+// A recorded sound. It is only played once it has finished loading, and until then (or for good, if
+// it can't be loaded) the synthesized version is played instead.
+pub enum Recorded {
+	// The path is kept to say which file is missing
+	Loading(Handle<AudioSource>, &'static str),
+	Loaded(Handle<AudioSource>),
+	Missing,
+}
+
+impl Recorded {
+	pub fn handle(&self) -> Option<Handle<AudioSource>> {
+		match self {
+			Recorded::Loaded(handle) => Some(handle.clone()),
+			_ => None,
+		}
+	}
+
+	// Settles on the recorded sound or the synthesized one, once loading has finished
+	fn resolve(&mut self, asset_server: &AssetServer) {
+		let Recorded::Loading(handle, path) = self else {
+			return;
+		};
+		match asset_server.load_state(&*handle) {
+			LoadState::Loaded => *self = Recorded::Loaded(handle.clone()),
+			LoadState::Failed(_) => {
+				warn!("Using a synthesized sound, as {path} could not be loaded");
+				*self = Recorded::Missing;
+			}
+			_ => {}
+		}
+	}
+}
+
+#[derive(Resource)]
+pub struct RecordedSounds {
+	pub gunshot: Recorded,
+	pub gunshot_deep: Recorded,
+	pub tankhit: Recorded,
+	pub tankhit_deep: Recorded,
+	pub wallhit: Recorded,
+	pub wallhit_deep: Recorded,
+}
+
+pub fn load_recorded_sounds(mut commands: Commands, asset_server: Res<AssetServer>) {
+	let load = |path: &'static str| Recorded::Loading(asset_server.load(path), path);
+	commands.insert_resource(RecordedSounds {
+		gunshot: load("ShotsFired.ogg"),
+		gunshot_deep: load("ShotsFiredDeep.ogg"),
+		tankhit: load("TankHit.ogg"),
+		tankhit_deep: load("TankHitDeep.ogg"),
+		wallhit: load("WallHit.ogg"),
+		wallhit_deep: load("WallHitDeep.ogg"),
+	});
+}
+
+pub fn resolve_recorded_sounds(asset_server: Res<AssetServer>, mut recorded: ResMut<RecordedSounds>) {
+	let RecordedSounds {
+		gunshot,
+		gunshot_deep,
+		tankhit,
+		tankhit_deep,
+		wallhit,
+		wallhit_deep,
+	} = &mut *recorded;
+	for sound in [gunshot, gunshot_deep, tankhit, tankhit_deep, wallhit, wallhit_deep] {
+		sound.resolve(&asset_server);
+	}
+}
+
 // A "one shot system" that will play a gunshot sound
 pub fn play_gunshot(
 	// Required to spawn new sounds
 	mut commands: Commands,
-	asset_server: Res<AssetServer>,
+	recorded: Res<RecordedSounds>,
+	synth: Res<SynthSounds>,
 	// Required to delete previous sounds
 	higher: Query<Entity, With<GunShotSound>>,
 	lower: Query<Entity, With<GunShotDeepSound>>,
@@ -38,24 +126,28 @@ pub fn play_gunshot(
 	// Here, we spawn the new sound
 	commands.spawn((
 		AudioBundle {
-			source: asset_server.load("ShotsFired.ogg"),
+			source: recorded.gunshot.handle().unwrap_or(synth.shot.clone()),
 			settings: PlaybackSettings::DESPAWN.with_volume(bevy::audio::Volume::new(0.5))
 		},
 		GunShotSound,
 	));
-	commands.spawn((
-		AudioBundle {
-			source: asset_server.load("ShotsFiredDeep.ogg"),
-			settings: PlaybackSettings::DESPAWN.with_volume(bevy::audio::Volume::new(0.25))
-		},
-		GunShotDeepSound,
-	));
+	// The synthesized fallback has no deep version, so there is nothing to play if this one is missing
+	if let Some(source) = recorded.gunshot_deep.handle() {
+		commands.spawn((
+			AudioBundle {
+				source,
+				settings: PlaybackSettings::DESPAWN.with_volume(bevy::audio::Volume::new(0.25))
+			},
+			GunShotDeepSound,
+		));
+	}
 }
 
 pub fn play_tankhit(
 	// Required to spawn new sounds
 	mut commands: Commands,
-	asset_server: Res<AssetServer>,
+	recorded: Res<RecordedSounds>,
+	synth: Res<SynthSounds>,
 	// Required to delete previous sounds
 	higher: Query<Entity, With<TankHitSound>>,
 	lower: Query<Entity, With<TankHitDeepSound>>,
@@ -72,24 +164,28 @@ pub fn play_tankhit(
 	// Here, we spawn the new sound
 	commands.spawn((
 		AudioBundle {
-			source: asset_server.load("TankHit.ogg"),
+			source: recorded.tankhit.handle().unwrap_or(synth.hit.clone()),
 			settings: PlaybackSettings::DESPAWN.with_volume(bevy::audio::Volume::new(0.5))
 		},
 		TankHitSound,
 	));
-	commands.spawn((
-		AudioBundle {
-			source: asset_server.load("TankHitDeep.ogg"),
-			settings: PlaybackSettings::DESPAWN.with_volume(bevy::audio::Volume::new(0.25))
-		},
-		TankHitDeepSound,
-	));
+	// The synthesized fallback has no deep version, so there is nothing to play if this one is missing
+	if let Some(source) = recorded.tankhit_deep.handle() {
+		commands.spawn((
+			AudioBundle {
+				source,
+				settings: PlaybackSettings::DESPAWN.with_volume(bevy::audio::Volume::new(0.25))
+			},
+			TankHitDeepSound,
+		));
+	}
 }
 
 pub fn play_wallhit(
 	// Required to spawn new sounds
 	mut commands: Commands,
-	asset_server: Res<AssetServer>,
+	recorded: Res<RecordedSounds>,
+	synth: Res<SynthSounds>,
 	// Required to delete previous sounds
 	higher: Query<Entity, With<WallHitSound>>,
 	lower: Query<Entity, With<WallHitDeepSound>>,
@@ -106,16 +202,145 @@ pub fn play_wallhit(
 	// Here, we spawn the new sound
 	commands.spawn((
 		AudioBundle {
-			source: asset_server.load("WallHit.ogg"),
+			source: recorded.wallhit.handle().unwrap_or(synth.hit.clone()),
 			settings: PlaybackSettings::DESPAWN.with_volume(bevy::audio::Volume::new(0.25))
 		},
 		WallHitSound,
 	));
+	// The synthesized fallback has no deep version, so there is nothing to play if this one is missing
+	if let Some(source) = recorded.wallhit_deep.handle() {
+		commands.spawn((
+			AudioBundle {
+				source,
+				settings: PlaybackSettings::DESPAWN.with_volume(bevy::audio::Volume::new(0.125))
+			},
+			WallHitDeepSound,
+		));
+	}
+}
+
+pub fn play_shieldhit(
+	mut commands: Commands,
+	synth: Res<SynthSounds>,
+	previous: Query<Entity, With<ShieldHitSound>>,
+) {
+	for entity in previous.iter() {
+		commands.entity(entity).despawn();
+	}
+
+	commands.spawn((
+		AudioBundle {
+			source: synth.shield_hit.clone(),
+			settings: PlaybackSettings::DESPAWN.with_volume(bevy::audio::Volume::new(0.4))
+		},
+		ShieldHitSound,
+	));
+}
+
+pub fn play_reload(
+	mut commands: Commands,
+	synth: Res<SynthSounds>,
+	previous: Query<Entity, With<ReloadSound>>,
+) {
+	for entity in previous.iter() {
+		commands.entity(entity).despawn();
+	}
+
 	commands.spawn((
 		AudioBundle {
-			source: asset_server.load("WallHitDeep.ogg"),
-			settings: PlaybackSettings::DESPAWN.with_volume(bevy::audio::Volume::new(0.125))
+			source: synth.reload.clone(),
+			settings: PlaybackSettings::DESPAWN.with_volume(bevy::audio::Volume::new(0.4))
 		},
-		WallHitDeepSound,
+		ReloadSound,
 	));
-}
\ No newline at end of file
+}
+
+pub fn play_explosion(
+	mut commands: Commands,
+	synth: Res<SynthSounds>,
+	previous: Query<Entity, With<ExplosionSound>>,
+) {
+	for entity in previous.iter() {
+		commands.entity(entity).despawn();
+	}
+
+	commands.spawn((
+		AudioBundle {
+			source: synth.explosion.clone(),
+			settings: PlaybackSettings::DESPAWN.with_volume(bevy::audio::Volume::new(0.5))
+		},
+		ExplosionSound,
+	));
+}
+
+pub fn play_pickup(
+	mut commands: Commands,
+	synth: Res<SynthSounds>,
+	previous: Query<Entity, With<PickupSound>>,
+) {
+	for entity in previous.iter() {
+		commands.entity(entity).despawn();
+	}
+
+	commands.spawn((
+		AudioBundle {
+			source: synth.pickup.clone(),
+			settings: PlaybackSettings::DESPAWN.with_volume(bevy::audio::Volume::new(0.5))
+		},
+		PickupSound,
+	));
+}
+
+pub fn play_bullet_clash(
+	mut commands: Commands,
+	synth: Res<SynthSounds>,
+	previous: Query<Entity, With<BulletClashSound>>,
+) {
+	for entity in previous.iter() {
+		commands.entity(entity).despawn();
+	}
+
+	commands.spawn((
+		AudioBundle {
+			source: synth.bullet_clash.clone(),
+			settings: PlaybackSettings::DESPAWN.with_volume(bevy::audio::Volume::new(0.4))
+		},
+		BulletClashSound,
+	));
+}
+
+pub fn play_countdown_beep(
+	mut commands: Commands,
+	synth: Res<SynthSounds>,
+	previous: Query<Entity, With<CountdownSound>>,
+) {
+	for entity in previous.iter() {
+		commands.entity(entity).despawn();
+	}
+
+	commands.spawn((
+		AudioBundle {
+			source: synth.countdown_beep.clone(),
+			settings: PlaybackSettings::DESPAWN.with_volume(bevy::audio::Volume::new(0.5))
+		},
+		CountdownSound,
+	));
+}
+
+pub fn play_countdown_go(
+	mut commands: Commands,
+	synth: Res<SynthSounds>,
+	previous: Query<Entity, With<CountdownSound>>,
+) {
+	for entity in previous.iter() {
+		commands.entity(entity).despawn();
+	}
+
+	commands.spawn((
+		AudioBundle {
+			source: synth.countdown_go.clone(),
+			settings: PlaybackSettings::DESPAWN.with_volume(bevy::audio::Volume::new(0.5))
+		},
+		CountdownSound,
+	));
+}
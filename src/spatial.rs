@@ -0,0 +1,85 @@
+use bevy::prelude::*;
+
+use crate::tanks::{Tank, TankStats};
+use crate::utils::{GridEntry, SpatialGrid};
+
+// Tanks are filed into a grid every fixed tick, so that collisions, bullet hits and pickups only
+// look at the tanks near them instead of every tank in the arena.
+
+// A little more than the widest tank, so most searches only touch a few cells
+pub const TANK_GRID_CELL_SIZE: f32 = 64.0;
+// The grid is built after tanks move, but tanks can still be pushed around before it is next
+// rebuilt, so searches reach this much further to make up for it
+pub const TANK_GRID_SLACK: f32 = 16.0;
+
+#[derive(Resource)]
+pub struct TankGrid(SpatialGrid);
+
+impl Default for TankGrid {
+    fn default() -> TankGrid {
+        TankGrid(SpatialGrid::new(TANK_GRID_CELL_SIZE))
+    }
+}
+
+impl TankGrid {
+    // Tanks whose edge could be within `range` of `point`. Callers still check the actual distance
+    // against the tank's current position.
+    pub fn nearby(&self, point: Vec2, range: f32) -> impl Iterator<Item = &GridEntry> + '_ {
+        self.0.nearby(point, range + TANK_GRID_SLACK)
+    }
+}
+
+pub fn rebuild_tank_grid(
+    mut grid: ResMut<TankGrid>,
+    tanks: Query<(Entity, &Transform, &TankStats), With<Tank>>,
+) {
+    grid.0.clear();
+    for (entity, transform, stats) in tanks.iter() {
+        grid.0
+            .insert(entity, transform.translation.truncate(), stats.radius);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    fn tank(world: &mut World, position: Vec2, radius: f32) -> Entity {
+        world
+            .spawn((
+                Tank,
+                Transform::from_translation(position.extend(0.0)),
+                TankStats {
+                    radius,
+                    max_speed: 6.0,
+                    acceleration: 0.6,
+                    friction: 0.9,
+                    mass: 1.0,
+                },
+            ))
+            .id()
+    }
+
+    #[test]
+    fn rebuilt_grid_finds_tanks_within_the_slack() {
+        let mut world = World::new();
+        world.insert_resource(TankGrid::default());
+        let near = tank(&mut world, Vec2::new(-100.0, 0.0), 20.0);
+        let far = tank(&mut world, Vec2::new(-1000.0, 0.0), 20.0);
+        world.run_system_once(rebuild_tank_grid);
+
+        let grid = world.resource::<TankGrid>();
+        // The tank's edge is out of range, but it could have been pushed into range since the
+        // grid was rebuilt
+        let range = 5.0;
+        let gap = range + TANK_GRID_SLACK - 1.0;
+        let point = Vec2::new(-100.0 + 20.0 + gap, 0.0);
+        let found: Vec<Entity> = grid
+            .nearby(point, range)
+            .map(|entry| entry.entity)
+            .collect();
+        assert!(found.contains(&near));
+        assert!(!found.contains(&far));
+    }
+}
@@ -0,0 +1,468 @@
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext, LoadState};
+use bevy::audio::AudioSource;
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::f32::consts::TAU;
+use std::fmt;
+
+// A tiny sfxr-style synthesizer. Every sound is described by a handful of parameters (a waveform,
+// a pitch that can slide up or down, and a volume envelope), which are turned into raw 16 bit PCM
+// and then wrapped in a WAV file so Bevy can play it like any other `AudioSource`. This lets new
+// weapons have their own sound without anyone having to record one, and the game still has sound
+// effects if the .ogg files are missing. Short looping tunes are made the same way, one note after
+// another, for when the music tracks are missing.
+//
+// The parameters of every sound are loaded from assets/sounds.synth.ron, so they can be tweaked
+// without touching the code. Until that file has loaded, or if it is missing or broken, the copy
+// built into the game is used instead.
+
+pub const SYNTH_PRESETS_PATH: &str = "sounds.synth.ron";
+const BUILT_IN_SYNTH_PRESETS: &str = include_str!("../assets/sounds.synth.ron");
+
+pub const SAMPLE_RATE: u32 = 44100;
+
+// Frequencies below this are inaudible anyway, and stop a downwards slide from going negative
+const MIN_FREQUENCY: f32 = 20.0;
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Waveform {
+    Square,
+    Sawtooth,
+    Sine,
+    Noise,
+}
+
+// Everything needed to generate a sound. All times are in seconds and all frequencies in Hz.
+#[derive(Deserialize, Clone, Debug)]
+pub struct SynthParams {
+    pub waveform: Waveform,
+    // The pitch the sound starts at
+    pub base_frequency: f32,
+    // How much the pitch changes per second. Negative values slide downwards.
+    pub frequency_slide: f32,
+    // Fraction of each cycle a square wave spends high. 0.5 is a "normal" square wave.
+    pub duty: f32,
+    // How far (as a fraction of the frequency) and how fast the pitch wobbles
+    pub vibrato_depth: f32,
+    pub vibrato_speed: f32,
+    // The volume envelope: fade in, hold, then fade out
+    pub attack: f32,
+    pub sustain: f32,
+    pub decay: f32,
+    pub volume: f32,
+}
+
+impl SynthParams {
+    // The total length of the sound
+    pub fn duration(&self) -> f32 {
+        self.attack + self.sustain + self.decay
+    }
+
+    // The envelope's volume multiplier at time `t`
+    fn envelope(&self, t: f32) -> f32 {
+        if t < self.attack {
+            t / self.attack
+        } else if t < self.attack + self.sustain {
+            1.0
+        } else if self.decay > 0.0 {
+            (1.0 - (t - self.attack - self.sustain) / self.decay).max(0.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+// Generates the raw mono PCM samples for a sound. The output only depends on the parameters (the
+// noise generator is seeded with a constant), so the same parameters always give the same samples.
+pub fn synthesize(params: &SynthParams) -> Vec<i16> {
+    let length = (params.duration() * SAMPLE_RATE as f32) as usize;
+    let mut samples = Vec::with_capacity(length);
+
+    let mut phase = 0.0_f32;
+    let mut noise_seed: u32 = 0x9E37_79B9;
+    let mut noise = next_noise(&mut noise_seed);
+
+    for i in 0..length {
+        let t = i as f32 / SAMPLE_RATE as f32;
+
+        let vibrato = 1.0 + (t * params.vibrato_speed * TAU).sin() * params.vibrato_depth;
+        let frequency =
+            ((params.base_frequency + params.frequency_slide * t) * vibrato).max(MIN_FREQUENCY);
+
+        // Phase goes from 0 to 1 once per cycle
+        phase += frequency / SAMPLE_RATE as f32;
+        if phase >= 1.0 {
+            phase = phase.fract();
+            // Like sfxr, noise picks a new random value once per cycle, so its "frequency" still
+            // controls how high or low it sounds
+            noise = next_noise(&mut noise_seed);
+        }
+
+        let wave = match params.waveform {
+            Waveform::Square => {
+                if phase < params.duty {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Sawtooth => 1.0 - 2.0 * phase,
+            Waveform::Sine => (phase * TAU).sin(),
+            Waveform::Noise => noise,
+        };
+
+        let sample = (wave * params.envelope(t) * params.volume).clamp(-1.0, 1.0);
+        samples.push((sample * i16::MAX as f32) as i16);
+    }
+
+    samples
+}
+
+// A xorshift random number generator, returning a value between -1 and 1
+fn next_noise(seed: &mut u32) -> f32 {
+    *seed ^= *seed << 13;
+    *seed ^= *seed >> 17;
+    *seed ^= *seed << 5;
+    *seed as f32 / u32::MAX as f32 * 2.0 - 1.0
+}
+
+// Wraps mono 16 bit PCM samples in a minimal WAV file
+pub fn encode_wav(samples: &[i16]) -> Vec<u8> {
+    let data_length = (samples.len() * 2) as u32;
+    let mut bytes = Vec::with_capacity(44 + data_length as usize);
+
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_length).to_le_bytes());
+    bytes.extend_from_slice(b"WAVE");
+
+    bytes.extend_from_slice(b"fmt ");
+    bytes.extend_from_slice(&16_u32.to_le_bytes()); // Size of this chunk
+    bytes.extend_from_slice(&1_u16.to_le_bytes()); // Uncompressed PCM
+    bytes.extend_from_slice(&1_u16.to_le_bytes()); // Mono
+    bytes.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    bytes.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes()); // Bytes per second
+    bytes.extend_from_slice(&2_u16.to_le_bytes()); // Bytes per sample
+    bytes.extend_from_slice(&16_u16.to_le_bytes()); // Bits per sample
+
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_length.to_le_bytes());
+    for sample in samples {
+        bytes.extend_from_slice(&sample.to_le_bytes());
+    }
+
+    bytes
+}
+
+pub fn to_audio_source(params: &SynthParams) -> AudioSource {
+    AudioSource {
+        bytes: encode_wav(&synthesize(params)).into(),
+    }
+}
+
+// A tune that loops, played on one instrument
+#[derive(Deserialize, Clone, Debug)]
+pub struct SynthLoop {
+    // Every note is this sound, at the note's frequency instead of `base_frequency`
+    pub instrument: SynthParams,
+    // Seconds from the start of one note to the start of the next
+    pub note_length: f32,
+    // The frequency of each note in Hz, where 0.0 is a rest
+    pub notes: Vec<f32>,
+}
+
+// Generates the samples of one time through a tune. Each note is cut off or padded with silence to
+// fill its `note_length`, so the loop is always exactly as long as its notes add up to.
+pub fn synthesize_loop(tune: &SynthLoop) -> Vec<i16> {
+    let note_samples = (tune.note_length * SAMPLE_RATE as f32) as usize;
+    let mut samples = Vec::with_capacity(note_samples * tune.notes.len());
+    for &frequency in &tune.notes {
+        let mut note = if frequency > 0.0 {
+            synthesize(&SynthParams {
+                base_frequency: frequency,
+                ..tune.instrument.clone()
+            })
+        } else {
+            Vec::new()
+        };
+        note.resize(note_samples, 0);
+        samples.extend(note);
+    }
+    samples
+}
+
+// The parameters every sound is generated from
+#[derive(Asset, TypePath, Deserialize, Clone, Debug)]
+pub struct SynthPresets {
+    pub shot: SynthParams,
+    pub hit: SynthParams,
+    pub shield_hit: SynthParams,
+    pub reload: SynthParams,
+    pub explosion: SynthParams,
+    pub pickup: SynthParams,
+    pub bullet_clash: SynthParams,
+    pub countdown_beep: SynthParams,
+    pub countdown_go: SynthParams,
+    // The music layers (see music.rs)
+    pub calm_music: SynthLoop,
+    pub combat_music: SynthLoop,
+}
+
+impl SynthPresets {
+    pub fn built_in() -> SynthPresets {
+        ron::de::from_str(BUILT_IN_SYNTH_PRESETS).expect("the built-in synth presets are valid")
+    }
+}
+
+#[derive(Debug)]
+pub enum SynthPresetsError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+}
+
+impl fmt::Display for SynthPresetsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SynthPresetsError::Io(error) => write!(f, "could not read synth presets: {error}"),
+            SynthPresetsError::Ron(error) => write!(f, "could not parse synth presets: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for SynthPresetsError {}
+
+impl From<std::io::Error> for SynthPresetsError {
+    fn from(error: std::io::Error) -> SynthPresetsError {
+        SynthPresetsError::Io(error)
+    }
+}
+
+impl From<ron::error::SpannedError> for SynthPresetsError {
+    fn from(error: ron::error::SpannedError) -> SynthPresetsError {
+        SynthPresetsError::Ron(error)
+    }
+}
+
+#[derive(Default)]
+pub struct SynthPresetsLoader;
+
+impl AssetLoader for SynthPresetsLoader {
+    type Asset = SynthPresets;
+    type Settings = ();
+    type Error = SynthPresetsError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<SynthPresets, SynthPresetsError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["synth.ron"]
+    }
+}
+
+#[derive(Resource)]
+pub struct SynthPresetsHandle(pub Handle<SynthPresets>);
+
+// Handles to the generated sounds, ready to be played
+#[derive(Resource)]
+pub struct SynthSounds {
+    pub shot: Handle<AudioSource>,
+    pub hit: Handle<AudioSource>,
+    pub shield_hit: Handle<AudioSource>,
+    pub reload: Handle<AudioSource>,
+    pub explosion: Handle<AudioSource>,
+    pub pickup: Handle<AudioSource>,
+    pub bullet_clash: Handle<AudioSource>,
+    pub countdown_beep: Handle<AudioSource>,
+    pub countdown_go: Handle<AudioSource>,
+    pub calm_music: Handle<AudioSource>,
+    pub combat_music: Handle<AudioSource>,
+}
+
+impl SynthSounds {
+    fn generate(presets: &SynthPresets, audio_sources: &mut Assets<AudioSource>) -> SynthSounds {
+        SynthSounds {
+            shot: audio_sources.add(to_audio_source(&presets.shot)),
+            hit: audio_sources.add(to_audio_source(&presets.hit)),
+            shield_hit: audio_sources.add(to_audio_source(&presets.shield_hit)),
+            reload: audio_sources.add(to_audio_source(&presets.reload)),
+            explosion: audio_sources.add(to_audio_source(&presets.explosion)),
+            pickup: audio_sources.add(to_audio_source(&presets.pickup)),
+            bullet_clash: audio_sources.add(to_audio_source(&presets.bullet_clash)),
+            countdown_beep: audio_sources.add(to_audio_source(&presets.countdown_beep)),
+            countdown_go: audio_sources.add(to_audio_source(&presets.countdown_go)),
+            calm_music: audio_sources.add(AudioSource {
+                bytes: encode_wav(&synthesize_loop(&presets.calm_music)).into(),
+            }),
+            combat_music: audio_sources.add(AudioSource {
+                bytes: encode_wav(&synthesize_loop(&presets.combat_music)).into(),
+            }),
+        }
+    }
+}
+
+// Generates the built-in sounds, so there is always something to play, and starts loading the
+// presets file
+pub fn create_synth_sounds(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut audio_sources: ResMut<Assets<AudioSource>>,
+) {
+    commands.insert_resource(SynthSounds::generate(
+        &SynthPresets::built_in(),
+        &mut audio_sources,
+    ));
+    commands.insert_resource(SynthPresetsHandle(asset_server.load(SYNTH_PRESETS_PATH)));
+}
+
+// Regenerates the sounds whenever the presets file is loaded or changed
+pub fn apply_synth_presets(
+    mut events: EventReader<AssetEvent<SynthPresets>>,
+    asset_server: Res<AssetServer>,
+    handle: Res<SynthPresetsHandle>,
+    presets: Res<Assets<SynthPresets>>,
+    mut sounds: ResMut<SynthSounds>,
+    mut audio_sources: ResMut<Assets<AudioSource>>,
+    mut warned: Local<bool>,
+) {
+    for event in events.read() {
+        if !event.is_loaded_with_dependencies(&handle.0) && !event.is_modified(&handle.0) {
+            continue;
+        }
+        if let Some(presets) = presets.get(&handle.0) {
+            *sounds = SynthSounds::generate(presets, &mut audio_sources);
+        }
+    }
+
+    if !*warned && matches!(asset_server.load_state(&handle.0), LoadState::Failed(_)) {
+        *warned = true;
+        warn!("Using the built-in synth presets, as {SYNTH_PRESETS_PATH} could not be loaded");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Lengths that add up exactly, so the sample count has no rounding to worry about
+    fn tone(waveform: Waveform) -> SynthParams {
+        SynthParams {
+            waveform,
+            base_frequency: 440.0,
+            frequency_slide: -200.0,
+            duty: 0.5,
+            vibrato_depth: 0.1,
+            vibrato_speed: 8.0,
+            attack: 0.125,
+            sustain: 0.25,
+            decay: 0.125,
+            volume: 0.5,
+        }
+    }
+
+    const WAVEFORMS: [Waveform; 4] = [
+        Waveform::Square,
+        Waveform::Sawtooth,
+        Waveform::Sine,
+        Waveform::Noise,
+    ];
+
+    fn u32_at(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn sample_count_matches_duration() {
+        let params = tone(Waveform::Square);
+        assert_eq!(params.duration(), 0.5);
+        assert_eq!(synthesize(&params).len(), SAMPLE_RATE as usize / 2);
+    }
+
+    #[test]
+    fn envelope_is_silent_at_both_ends() {
+        for waveform in WAVEFORMS {
+            let samples = synthesize(&tone(waveform));
+            assert_eq!(samples[0], 0, "{waveform:?} should fade in from silence");
+            let last = samples.last().unwrap().unsigned_abs();
+            assert!(
+                last < 16,
+                "{waveform:?} should have faded out, but ends at {last}"
+            );
+        }
+    }
+
+    #[test]
+    fn peak_stays_within_volume() {
+        for waveform in WAVEFORMS {
+            let params = tone(waveform);
+            let limit = params.volume * i16::MAX as f32;
+            let peak = synthesize(&params)
+                .iter()
+                .map(|sample| sample.unsigned_abs())
+                .max()
+                .unwrap() as f32;
+            assert!(peak <= limit, "{waveform:?} peaks at {peak}, over {limit}");
+            // And the sound isn't just silent
+            assert!(peak > limit * 0.5, "{waveform:?} only peaks at {peak}");
+        }
+    }
+
+    #[test]
+    fn same_params_give_same_samples() {
+        let params = tone(Waveform::Noise);
+        assert_eq!(synthesize(&params), synthesize(&params));
+    }
+
+    #[test]
+    fn wav_header_describes_the_samples() {
+        let samples = synthesize(&tone(Waveform::Sine));
+        let bytes = encode_wav(&samples);
+        let data_length = samples.len() as u32 * 2;
+
+        assert_eq!(bytes.len(), 44 + data_length as usize);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(u32_at(&bytes, 4), bytes.len() as u32 - 8);
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(u32_at(&bytes, 24), SAMPLE_RATE);
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(u32_at(&bytes, 40), data_length);
+        assert_eq!(&bytes[44..46], &samples[0].to_le_bytes());
+    }
+
+    #[test]
+    fn loop_is_as_long_as_its_notes() {
+        let tune = SynthLoop {
+            instrument: tone(Waveform::Sine),
+            note_length: 0.25,
+            notes: vec![220.0, 0.0, 330.0],
+        };
+        let samples = synthesize_loop(&tune);
+        let note_samples = SAMPLE_RATE as usize / 4;
+        assert_eq!(samples.len(), note_samples * 3);
+        // The rest is silent, and the notes aren't
+        assert!(samples[note_samples..note_samples * 2]
+            .iter()
+            .all(|&sample| sample == 0));
+        assert!(samples[..note_samples].iter().any(|&sample| sample != 0));
+        assert!(samples[note_samples * 2..]
+            .iter()
+            .any(|&sample| sample != 0));
+    }
+
+    #[test]
+    fn built_in_presets_parse() {
+        let presets = SynthPresets::built_in();
+        assert!(presets.shot.duration() > 0.0);
+        assert!(presets.countdown_go.duration() > 0.0);
+        // The music layers crossfade, so they have to stay in time with each other
+        let length = |tune: &SynthLoop| synthesize_loop(tune).len();
+        assert_eq!(length(&presets.calm_music), length(&presets.combat_music));
+    }
+}
@@ -0,0 +1,153 @@
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext, LoadState};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+
+// Tank classes are loaded from assets/tanks.classes.ron, so new kinds of tank can be added and
+// balanced without touching the code. If that file is missing or broken, the copy built into the
+// game is used instead, so there are always classes to spawn.
+
+pub const TANK_CLASSES_PATH: &str = "tanks.classes.ron";
+const BUILT_IN_TANK_CLASSES: &str = include_str!("../assets/tanks.classes.ron");
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct WeaponClass {
+    // Seconds between shots
+    pub cooldown: f32,
+    // Pixels per tick
+    pub bullet_speed: f32,
+    pub bullet_damage: f32,
+    // The bullet's radius
+    pub bullet_size: f32,
+}
+
+// Colours as sRGB. The body is coloured by team, and the rest by class.
+#[derive(Deserialize, Clone, Debug)]
+pub struct ClassColours {
+    pub player: (f32, f32, f32),
+    pub ai: (f32, f32, f32),
+    pub outline: (f32, f32, f32),
+    pub turret: (f32, f32, f32),
+}
+
+pub fn srgb((red, green, blue): (f32, f32, f32)) -> Color {
+    Color::srgb(red, green, blue)
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct TankClass {
+    pub radius: f32,
+    // Pixels per tick
+    pub max_speed: f32,
+    // Speed gained per tick at full throttle
+    pub acceleration: f32,
+    // The fraction of its speed the tank keeps each tick
+    pub friction: f32,
+    pub mass: f32,
+    pub health: f32,
+    // Radians per second
+    pub turret_turn_rate: f32,
+    pub weapon: WeaponClass,
+    pub colours: ClassColours,
+}
+
+// Every class, by name
+#[derive(Asset, TypePath, Clone, Debug)]
+pub struct TankClasses(pub HashMap<String, TankClass>);
+
+#[derive(Debug)]
+pub enum TankClassesError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+}
+
+impl fmt::Display for TankClassesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TankClassesError::Io(error) => write!(f, "could not read tank classes: {error}"),
+            TankClassesError::Ron(error) => write!(f, "could not parse tank classes: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for TankClassesError {}
+
+impl From<std::io::Error> for TankClassesError {
+    fn from(error: std::io::Error) -> TankClassesError {
+        TankClassesError::Io(error)
+    }
+}
+
+impl From<ron::error::SpannedError> for TankClassesError {
+    fn from(error: ron::error::SpannedError) -> TankClassesError {
+        TankClassesError::Ron(error)
+    }
+}
+
+#[derive(Default)]
+pub struct TankClassesLoader;
+
+impl AssetLoader for TankClassesLoader {
+    type Asset = TankClasses;
+    type Settings = ();
+    type Error = TankClassesError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<TankClasses, TankClassesError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(TankClasses(ron::de::from_bytes(&bytes)?))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["classes.ron"]
+    }
+}
+
+#[derive(Resource)]
+pub struct TankClassesHandle(pub Handle<TankClasses>);
+
+pub fn load_tank_classes(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(TankClassesHandle(asset_server.load(TANK_CLASSES_PATH)));
+}
+
+// Swaps in the built-in classes if the file couldn't be loaded
+pub fn fall_back_to_built_in_classes(
+    asset_server: Res<AssetServer>,
+    handle: Res<TankClassesHandle>,
+    mut classes: ResMut<Assets<TankClasses>>,
+) {
+    if classes.contains(&handle.0)
+        || !matches!(asset_server.load_state(&handle.0), LoadState::Failed(_))
+    {
+        return;
+    }
+    warn!("Using the built-in tank classes, as {TANK_CLASSES_PATH} could not be loaded");
+    let built_in =
+        ron::de::from_str(BUILT_IN_TANK_CLASSES).expect("the built-in tank classes are valid");
+    classes.insert(&handle.0, TankClasses(built_in));
+}
+
+// Looks up tank classes by name, once they have loaded
+#[derive(SystemParam)]
+pub struct TankClassLibrary<'w> {
+    handle: Res<'w, TankClassesHandle>,
+    classes: Res<'w, Assets<TankClasses>>,
+}
+
+impl TankClassLibrary<'_> {
+    pub fn is_loaded(&self) -> bool {
+        self.classes.contains(&self.handle.0)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&TankClass> {
+        self.classes.get(&self.handle.0)?.0.get(name)
+    }
+}
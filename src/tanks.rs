@@ -1,12 +1,24 @@
-// TODO: Fix bearing using a sprite bundle
-
-use crate::utils::Health;
+use crate::interpolation::Interpolated;
+use crate::pickups::ActiveEffects;
+use crate::tank_classes::{srgb, TankClass};
+use crate::utils::{angle_between, Health};
 use bevy::prelude::*;
-use bevy::sprite::Mesh2dHandle;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
 
-pub const TANK_SPEED: f32 = 2.0 / 3.0;
+// The radius of a medium tank. Things that aren't tied to a particular tank's size (such as how
+// far from the walls pickups spawn) are sized around this. Each tank's own size is in TankStats.
 pub const TANK_SIZE: f32 = 20.0;
-const TURRET_SIZE: f32 = 16.0;
+// How close to their target turrets have to be pointing before they can fire, in radians
+pub const TURRET_AIM_TOLERANCE: f32 = 0.15;
+// How far the turret sticks out past the edge of the hull when it isn't recoiling
+pub const TURRET_REACH: f32 = 4.0;
+// The width of the black outline around the coloured part of the hull
+const OUTLINE_WIDTH: f32 = 4.0;
+// The treads run along either side of the hull, sticking out of the front and back
+const TREAD_INSET: f32 = 6.0;
+const TREAD_OVERHANG: f32 = 2.0;
+const TREAD_WIDTH: f32 = 9.0;
+const TREAD_COLOUR: Color = Color::srgb(0.15, 0.15, 0.15);
 
 #[derive(Component)]
 pub struct Tank;
@@ -23,6 +35,33 @@ pub struct Velocity {
     pub value: Vec2,
 }
 
+// How a tank handles, from its class (see tank_classes.rs)
+#[derive(Component, Clone)]
+pub struct TankStats {
+    pub radius: f32,
+    // Pixels per tick
+    pub max_speed: f32,
+    // Speed gained per tick while driving
+    pub acceleration: f32,
+    // The fraction of its speed the tank keeps each tick
+    pub friction: f32,
+    pub mass: f32,
+}
+
+impl TankStats {
+    // How far the treads are from the middle of the tank
+    pub fn tread_offset(&self) -> f32 {
+        self.radius - TREAD_INSET
+    }
+}
+
+// Which side a tank is on
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Team {
+    Player,
+    Ai,
+}
+
 // For whether an AI is active or not
 #[derive(Component)]
 pub struct Active {
@@ -35,18 +74,126 @@ pub struct AttackTimer {
     pub value: f32,
 }
 
+// The tank's gun
+#[derive(Component)]
+pub struct Weapon {
+    // Seconds between shots
+    pub cooldown: f32,
+    // Pixels per tick
+    pub bullet_speed: f32,
+    pub bullet_damage: f32,
+    pub bullet_size: f32,
+}
+
+impl Weapon {
+    // How ready the weapon is to fire again, from 0.0 (just fired) to 1.0 (ready). This assumes the
+    // AttackTimer counts up from the last shot, as the player's does.
+    pub fn readiness(&self, attack_timer: &AttackTimer, effects: &ActiveEffects) -> f32 {
+        (attack_timer.value / (self.cooldown * effects.cooldown_multiplier())).clamp(0.0, 1.0)
+    }
+}
+
+// Optional limited ammunition for a Weapon. Tanks without this have unlimited fire.
+#[derive(Component)]
+pub struct Magazine {
+    // How many shots a full magazine holds, and how many are in it now
+    pub size: u32,
+    pub loaded: u32,
+    // Spare ammunition that reloads are taken from. None means the reserve never runs out.
+    pub reserve: Option<u32>,
+    // Seconds a reload takes
+    pub reload_time: f32,
+    // Seconds spent reloading so far, or None if not reloading
+    pub reloading: Option<f32>,
+}
+
+impl Magazine {
+    pub fn new(size: u32, reserve: Option<u32>, reload_time: f32) -> Magazine {
+        Magazine {
+            size,
+            loaded: size,
+            reserve,
+            reload_time,
+            reloading: None,
+        }
+    }
+
+    pub fn can_fire(&self) -> bool {
+        self.loaded > 0 && self.reloading.is_none()
+    }
+
+    pub fn fire(&mut self) {
+        self.loaded = self.loaded.saturating_sub(1);
+    }
+
+    // A reload is only worth it if the magazine isn't full and there is ammo left to put in it
+    pub fn can_reload(&self) -> bool {
+        self.reloading.is_none() && self.loaded < self.size && self.reserve != Some(0)
+    }
+
+    // Returns whether a reload was started
+    pub fn start_reload(&mut self) -> bool {
+        if self.can_reload() {
+            self.reloading = Some(0.0);
+            true
+        } else {
+            false
+        }
+    }
+
+    // How far through the reload the magazine is, from 0.0 to 1.0
+    pub fn reload_progress(&self) -> Option<f32> {
+        self.reloading
+            .map(|elapsed| (elapsed / self.reload_time).min(1.0))
+    }
+
+    // Fills the magazine from the reserve
+    pub fn finish_reload(&mut self) {
+        let wanted = self.size - self.loaded;
+        let taken = match self.reserve {
+            Some(reserve) => wanted.min(reserve),
+            None => wanted,
+        };
+        self.loaded += taken;
+        if let Some(reserve) = self.reserve.as_mut() {
+            *reserve -= taken;
+        }
+        self.reloading = None;
+    }
+}
+
 // Time since last got damage
 #[derive(Component)]
 pub struct HitTimer(pub f32);
 
-// So that the flash yellow on damage system knows what colour to return to
+// Out-of-combat healing. Once `delay` seconds have passed since the tank was last hit (see
+// HitTimer), it regains `rate` health per second. Tanks without this component don't regenerate.
+#[derive(Component)]
+pub struct Regeneration {
+    pub delay: f32,
+    pub rate: f32,
+}
+
+// So that the flash yellow on damage system knows what colour to return to. Every part of a tank
+// with this (and a ColorMaterial) flashes when the tank is hit.
 #[derive(Component)]
 pub struct OriginalColour(pub Color);
 
-// How long an AI is continuing in a direction for
+// The body and treads of a tank, which turn to face the way the tank is moving. The turret is on
+// the Bearing instead, so it can aim independently.
+#[derive(Component)]
+pub struct Hull;
+
+// Where a tank last left tread marks
+#[derive(Component)]
+pub struct TreadTrail {
+    pub last: Vec2,
+}
+
+// How many more ticks an AI keeps going in the same direction for
 #[derive(Component)]
 pub struct Steps {
-    pub value: f32,
+    pub value: u32,
 }
 
 // The direction an AI is moving in
@@ -58,20 +205,56 @@ pub struct DirectionAi {
 #[derive(Component)]
 pub struct Turret;
 
+// How fast a tank's turret can turn (in radians per second), and how close to its target (in
+// radians) it has to be pointing before the tank can fire
+#[derive(Component)]
+pub struct TurretTraverse {
+    pub speed: f32,
+    pub tolerance: f32,
+}
+
+impl TurretTraverse {
+    // Turns from `current` towards `target`, as far as the turret can turn in `delta` seconds
+    pub fn turn(&self, current: f32, target: f32, delta: f32) -> f32 {
+        let max_turn = self.speed * delta;
+        current + angle_between(current, target).clamp(-max_turn, max_turn)
+    }
+
+    pub fn on_target(&self, current: f32, target: f32) -> bool {
+        angle_between(current, target).abs() <= self.tolerance
+    }
+}
+
 // The bearing entity is a child of a tank and the parent to the turret
+//
+// The full hierarchy of a tank is:
+//
+//     Tank
+//     ├── Hull
+//     │   ├── Tread (x2)
+//     │   ├── Outline
+//     │   └── Body
+//     ├── Bearing
+//     │   └── Turret
+//     └── bars and icons
 #[derive(Component)]
 pub struct Bearing;
 
 // Defines a Tank Bundle that can spawn a tank in a single commands.spawn(TankBundle{ ... })
 #[derive(Bundle)]
-pub struct TankBundle<M: bevy::sprite::Material2d> {
+pub struct TankBundle {
     tank: Tank, // Marker component
-    material_bundle: bevy::sprite::MaterialMesh2dBundle<M>, // Colour
+    spatial_bundle: SpatialBundle,
     attack_timer: AttackTimer,
+    stats: TankStats,
+    weapon: Weapon,
+    turret_traverse: TurretTraverse,
     hit_timer: HitTimer,
     health: Health,
     velocity: Velocity,
-    original_colour: OriginalColour,
+    active_effects: ActiveEffects,
+    tread_trail: TreadTrail,
+    interpolated: Interpolated,
 }
 
 // The AI Bundle is an extension to the Tank Bundle
@@ -85,85 +268,87 @@ pub struct AiBundle {
 
 #[derive(Bundle)]
 pub struct BearingBundle {
-    // Sprite bundle is used to give the bearing a translation
-    // This is not necessary in later Bevy versions as the SpatialBundle exists
-    sprite_bundle: SpriteBundle,
+    spatial_bundle: SpatialBundle,
     bearing: Bearing,
+    interpolated: Interpolated,
 }
 
 impl BearingBundle {
     pub fn new() -> BearingBundle {
         BearingBundle {
-            sprite_bundle: SpriteBundle {
-                sprite: Sprite {
-                    color: Color::NONE,
-                    ..default()
-                },
-                transform: Transform {
-                    scale: Vec3::new(1.0, 1.0, 1.0),
-                    translation: Vec3::new(0.0, 0.0, 0.),
-                    ..default()
-                },
-                ..default()
-            },
+            spatial_bundle: SpatialBundle::default(),
             bearing: Bearing,
+            interpolated: Interpolated::default(),
         }
     }
 }
 
 #[derive(Bundle)]
 pub struct TurretBundle {
-    sprite_bundle: SpriteBundle,
+    material_bundle: MaterialMesh2dBundle<ColorMaterial>,
     turret: Turret,
     original_color: OriginalColour,
+    interpolated: Interpolated,
 }
 
 impl TurretBundle {
-    pub fn new() -> TurretBundle {
+    pub fn new(
+        meshes: &mut ResMut<Assets<Mesh>>,
+        materials: &mut ResMut<Assets<ColorMaterial>>,
+        radius: f32,
+        colour: Color,
+    ) -> TurretBundle {
+        let size = radius * 0.8;
         TurretBundle {
-            sprite_bundle: SpriteBundle {
-                sprite: Sprite {
-                    color: Color::srgb(0., 0., 0.),
-                    ..default()
-                },
-                transform: Transform {
-                    scale: Vec3::new(TURRET_SIZE, TURRET_SIZE, 0.),
-                    translation: Vec3::new(TANK_SIZE + 4.0, 0.0, -1.0), // The "TANK_SIZE+4.0" is reset every frame due to a system anyway
-                    ..default()
-                },
+            material_bundle: MaterialMesh2dBundle {
+                mesh: Mesh2dHandle(meshes.add(Rectangle::new(size, size))),
+                material: materials.add(colour),
+                // The "radius + TURRET_REACH" is reset every frame due to a system anyway. The
+                // turret sits under the hull, so only the barrel sticking out of it shows.
+                transform: Transform::from_xyz(radius + TURRET_REACH, 0.0, -0.1),
                 ..default()
             },
             turret: Turret,
-            original_color: OriginalColour(Color::BLACK),
+            original_color: OriginalColour(colour),
+            interpolated: Interpolated::default(),
         }
     }
 }
 
 // TANK BUNDLE
-impl TankBundle<ColorMaterial> {
-    pub fn new(
-        meshes: &mut ResMut<Assets<Mesh>>,
-        materials: &mut ResMut<Assets<ColorMaterial>>,
-        health: u8,
-    ) -> TankBundle<ColorMaterial> {
+impl TankBundle {
+    pub fn new(class: &TankClass, position: Vec2) -> TankBundle {
         TankBundle {
-            material_bundle: bevy::sprite::MaterialMesh2dBundle {
-                mesh: Mesh2dHandle(meshes.add(Circle { radius: TANK_SIZE })),
-                material: materials.add(ColorMaterial::from(Color::BLACK)),
-                transform: Transform {
-                    translation: Vec3::new(0.0, 0.0, 1.0),
-                    ..default()
-                },
-                ..default()
-            },
+            spatial_bundle: SpatialBundle::from_transform(Transform::from_translation(
+                position.extend(1.0),
+            )),
             tank: Tank,
             attack_timer: AttackTimer { value: 0.0 },
+            stats: TankStats {
+                radius: class.radius,
+                max_speed: class.max_speed,
+                acceleration: class.acceleration,
+                friction: class.friction,
+                mass: class.mass,
+            },
+            weapon: Weapon {
+                cooldown: class.weapon.cooldown,
+                bullet_speed: class.weapon.bullet_speed,
+                bullet_damage: class.weapon.bullet_damage,
+                bullet_size: class.weapon.bullet_size,
+            },
+            turret_traverse: TurretTraverse {
+                speed: class.turret_turn_rate,
+                tolerance: TURRET_AIM_TOLERANCE,
+            },
             hit_timer: HitTimer(1.0),
-            health: Health { value: health },
+            health: Health::new(class.health),
             velocity: Velocity {
                 value: Vec2::new(0.0, 0.0),
             },
-            original_colour: OriginalColour(Color::BLACK),
+            active_effects: ActiveEffects::default(),
+            tread_trail: TreadTrail { last: position },
+            interpolated: Interpolated::default(),
         }
     }
 }
@@ -173,9 +358,71 @@ impl AiBundle {
     pub fn new() -> AiBundle {
         AiBundle {
             active: Active { value: true },
-            steps: Steps { value: 0.0 },
+            steps: Steps { value: 0 },
             direction_ai: DirectionAi { value: 0 },
             ai: Ai,
         }
     }
 }
+
+// Spawns the visible parts of a tank (its hull and turret) as children of the tank. The body is
+// coloured by team, and everything else by class.
+pub fn spawn_tank_parts(
+    parent: &mut ChildBuilder,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    class: &TankClass,
+    team: Team,
+) {
+    let radius = class.radius;
+    let body_colour = srgb(match team {
+        Team::Player => class.colours.player,
+        Team::Ai => class.colours.ai,
+    });
+    let outline_colour = srgb(class.colours.outline);
+    parent
+        .spawn((SpatialBundle::default(), Hull, Name::new("Hull")))
+        .with_children(|parent| {
+            let tread = Mesh2dHandle(
+                meshes.add(Rectangle::new((radius + TREAD_OVERHANG) * 2.0, TREAD_WIDTH)),
+            );
+            for side in [-1.0, 1.0] {
+                parent.spawn((
+                    MaterialMesh2dBundle {
+                        mesh: tread.clone(),
+                        material: materials.add(TREAD_COLOUR),
+                        transform: Transform::from_xyz(0.0, side * (radius - TREAD_INSET), -0.2),
+                        ..default()
+                    },
+                    OriginalColour(TREAD_COLOUR),
+                ));
+            }
+            parent.spawn((
+                MaterialMesh2dBundle {
+                    mesh: Mesh2dHandle(meshes.add(Circle { radius })),
+                    material: materials.add(outline_colour),
+                    ..default()
+                },
+                OriginalColour(outline_colour),
+            ));
+            parent.spawn((
+                MaterialMesh2dBundle {
+                    mesh: Mesh2dHandle(meshes.add(Circle {
+                        radius: radius - OUTLINE_WIDTH,
+                    })),
+                    material: materials.add(body_colour),
+                    transform: Transform::from_xyz(0.0, 0.0, 0.1),
+                    ..default()
+                },
+                OriginalColour(body_colour),
+            ));
+        });
+    parent.spawn(BearingBundle::new()).with_children(|parent| {
+        parent.spawn(TurretBundle::new(
+            meshes,
+            materials,
+            radius,
+            srgb(class.colours.turret),
+        ));
+    });
+}
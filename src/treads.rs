@@ -0,0 +1,122 @@
+use bevy::prelude::*;
+use std::f32::consts::PI;
+
+use crate::driving::TankDrive;
+use crate::tanks::{Hull, Tank, TankStats, TreadTrail, Velocity};
+use crate::utils::angle_between;
+
+// Hulls turn to face the way their tank is moving, and tanks leave tread marks behind them that
+// fade away over time.
+
+// How fast a hull turns, in radians per second
+const HULL_TURN_SPEED: f32 = 8.0;
+// Tanks slower than this (in pixels per tick) are treated as stopped, so their hull stays put
+// rather than jittering around as they come to a halt
+const MIN_TURN_SPEED: f32 = 0.3;
+
+// How far a tank moves between each pair of marks
+const TREAD_MARK_SPACING: f32 = 6.0;
+const TREAD_MARK_SIZE: Vec2 = Vec2::new(4.0, 7.0);
+const TREAD_MARK_COLOUR: Color = Color::srgba(0.35, 0.25, 0.18, 0.35);
+const TREAD_MARK_FADE_TIME: f32 = 6.0;
+// On the floor, below scorch marks
+const TREAD_MARK_Z: f32 = -29.5;
+
+#[derive(Component)]
+pub struct TreadMark {
+    age: f32,
+}
+
+pub fn rotate_hulls(
+    time: Res<Time>,
+    tanks: Query<(&Velocity, &Children, Option<&TankDrive>), With<Tank>>,
+    mut hulls: Query<&mut Transform, With<Hull>>,
+) {
+    for (velocity, children, drive) in tanks.iter() {
+        // Tanks with tank controls steer their hull directly
+        if let Some(drive) = drive {
+            for &child in children.iter() {
+                if let Ok(mut hull) = hulls.get_mut(child) {
+                    hull.rotation = Quat::from_rotation_z(drive.heading);
+                }
+            }
+            continue;
+        }
+        if velocity.value.length() < MIN_TURN_SPEED {
+            continue;
+        }
+        let target = velocity.value.to_angle();
+        for &child in children.iter() {
+            let Ok(mut hull) = hulls.get_mut(child) else {
+                continue;
+            };
+            let current = hull.rotation.to_euler(EulerRot::ZYX).0;
+            // Treads work just as well backwards, so a tank reversing turns the shorter way round
+            // rather than spinning all the way around
+            let mut turn = angle_between(current, target);
+            if turn.abs() > PI / 2.0 {
+                turn = angle_between(current, target + PI);
+            }
+            let max_turn = HULL_TURN_SPEED * time.delta_seconds();
+            hull.rotation = Quat::from_rotation_z(current + turn.clamp(-max_turn, max_turn));
+        }
+    }
+}
+
+pub fn leave_tread_marks(
+    mut commands: Commands,
+    mut tanks: Query<(&Transform, &TankStats, &mut TreadTrail, &Children), With<Tank>>,
+    hulls: Query<&Transform, (With<Hull>, Without<Tank>)>,
+) {
+    for (transform, stats, mut trail, children) in tanks.iter_mut() {
+        let position = transform.translation.truncate();
+        if position.distance(trail.last) < TREAD_MARK_SPACING {
+            continue;
+        }
+        // A tank that has jumped a long way (such as when it first spawns) shouldn't leave marks
+        let jumped = position.distance(trail.last) > TREAD_MARK_SPACING * 4.0;
+        trail.last = position;
+        if jumped {
+            continue;
+        }
+
+        let Some(hull) = children.iter().find_map(|&child| hulls.get(child).ok()) else {
+            continue;
+        };
+        for side in [-1.0, 1.0] {
+            let offset = hull.rotation * Vec3::new(0.0, side * stats.tread_offset(), 0.0);
+            commands.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: TREAD_MARK_COLOUR,
+                        custom_size: Some(TREAD_MARK_SIZE),
+                        ..default()
+                    },
+                    transform: Transform {
+                        translation: (position + offset.truncate()).extend(TREAD_MARK_Z),
+                        rotation: hull.rotation,
+                        ..default()
+                    },
+                    ..default()
+                },
+                TreadMark { age: 0.0 },
+            ));
+        }
+    }
+}
+
+pub fn update_tread_marks(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut marks: Query<(Entity, &mut TreadMark, &mut Sprite)>,
+) {
+    for (entity, mut mark, mut sprite) in marks.iter_mut() {
+        mark.age += time.delta_seconds();
+        if mark.age >= TREAD_MARK_FADE_TIME {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let fade = 1.0 - mark.age / TREAD_MARK_FADE_TIME;
+        sprite.color = TREAD_MARK_COLOUR.with_alpha(TREAD_MARK_COLOUR.alpha() * fade);
+    }
+}
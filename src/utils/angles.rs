@@ -0,0 +1,7 @@
+use std::f32::consts::{PI, TAU};
+
+// The signed difference between two angles in radians, wrapped to between -PI and PI, so that
+// turning by it always goes the short way round
+pub fn angle_between(from: f32, to: f32) -> f32 {
+    (to - from + PI).rem_euclid(TAU) - PI
+}
@@ -0,0 +1,162 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+// A uniform grid for finding what is near a point without checking everything. Each entry is filed
+// under the cell its centre is in, and searches look through every cell close enough to hold an
+// entry that could reach them. This file doesn't depend on the rest of the game, so that the
+// spatial_grid example can benchmark it on its own.
+
+#[derive(Clone, Copy, Debug)]
+pub struct GridEntry {
+    pub entity: Entity,
+    pub radius: f32,
+}
+
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<IVec2, Vec<GridEntry>>,
+    // Searches reach this much further, so that big entries filed in a neighbouring cell are found
+    largest_radius: f32,
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f32) -> SpatialGrid {
+        SpatialGrid {
+            cell_size,
+            cells: HashMap::default(),
+            largest_radius: 0.0,
+        }
+    }
+
+    // Empties the grid. The cells keep their memory, as the grid is refilled every tick.
+    pub fn clear(&mut self) {
+        for cell in self.cells.values_mut() {
+            cell.clear();
+        }
+        self.largest_radius = 0.0;
+    }
+
+    pub fn insert(&mut self, entity: Entity, position: Vec2, radius: f32) {
+        let cell = self.cell(position);
+        self.cells
+            .entry(cell)
+            .or_default()
+            .push(GridEntry { entity, radius });
+        self.largest_radius = self.largest_radius.max(radius);
+    }
+
+    fn cell(&self, point: Vec2) -> IVec2 {
+        (point / self.cell_size).floor().as_ivec2()
+    }
+
+    // Every entry whose edge could be within `range` of `point`. This includes some that are further
+    // away, so callers still check the actual distance.
+    pub fn nearby(&self, point: Vec2, range: f32) -> impl Iterator<Item = &GridEntry> + '_ {
+        let reach = Vec2::splat(range.max(0.0) + self.largest_radius);
+        let min = self.cell(point - reach);
+        let max = self.cell(point + reach);
+        (min.x..=max.x)
+            .flat_map(move |x| (min.y..=max.y).map(move |y| IVec2::new(x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CELL_SIZE: f32 = 64.0;
+
+    fn found(grid: &SpatialGrid, point: Vec2, range: f32) -> Vec<u32> {
+        let mut found: Vec<u32> = grid
+            .nearby(point, range)
+            .map(|entry| entry.entity.index())
+            .collect();
+        found.sort();
+        found
+    }
+
+    fn grid_of(entries: &[(Vec2, f32)]) -> SpatialGrid {
+        let mut grid = SpatialGrid::new(CELL_SIZE);
+        for (i, (position, radius)) in entries.iter().enumerate() {
+            grid.insert(Entity::from_raw(i as u32), *position, *radius);
+        }
+        grid
+    }
+
+    #[test]
+    fn finds_entries_across_a_cell_boundary() {
+        let grid = grid_of(&[(Vec2::new(63.9, 0.0), 1.0)]);
+        assert_eq!(found(&grid, Vec2::new(64.1, 0.0), 1.0), [0]);
+        assert_eq!(found(&grid, Vec2::new(64.0, 63.9), 1.0), [0]);
+    }
+
+    #[test]
+    fn finds_entries_at_negative_coordinates() {
+        let grid = grid_of(&[
+            (Vec2::new(-0.5, -0.5), 1.0),
+            (Vec2::new(-200.0, -10.0), 5.0),
+        ]);
+        // Either side of the origin, which is where rounding towards zero would go wrong
+        assert_eq!(found(&grid, Vec2::new(0.5, 0.5), 1.0), [0]);
+        assert_eq!(found(&grid, Vec2::new(-190.0, -10.0), 6.0), [1]);
+        assert!(found(&grid, Vec2::new(-400.0, -400.0), 1.0).is_empty());
+    }
+
+    #[test]
+    fn finds_entries_bigger_than_a_cell() {
+        // Filed four cells away from where its edge is
+        let grid = grid_of(&[(Vec2::ZERO, 200.0), (Vec2::new(600.0, 0.0), 2.0)]);
+        assert_eq!(found(&grid, Vec2::new(205.0, 0.0), 10.0), [0]);
+        assert_eq!(found(&grid, Vec2::new(-140.0, -140.0), 1.0), [0]);
+    }
+
+    #[test]
+    fn finds_entries_with_a_range_bigger_than_a_cell() {
+        let grid = grid_of(&[(Vec2::new(300.0, -300.0), 5.0)]);
+        assert_eq!(found(&grid, Vec2::ZERO, 420.0), [0]);
+    }
+
+    // Compares with checking every entry, over a spread of positions and sizes either side of zero
+    #[test]
+    fn finds_everything_in_reach_once() {
+        let mut entries = Vec::new();
+        for i in 0..400 {
+            let position = Vec2::new((i * 37 % 641) as f32 - 320.0, (i * 53 % 577) as f32 - 288.0);
+            entries.push((position, [2.0, 16.0, 25.0, 90.0][i % 4]));
+        }
+        let grid = grid_of(&entries);
+
+        for (point, range) in [
+            (Vec2::ZERO, 10.0),
+            (Vec2::new(-64.0, -64.0), 0.0),
+            (Vec2::new(127.9, -128.1), 40.0),
+            (Vec2::new(-300.0, 250.0), 150.0),
+        ] {
+            let in_reach: Vec<u32> = (0..entries.len() as u32)
+                .filter(|&i| {
+                    let (position, radius) = entries[i as usize];
+                    position.distance(point) <= range + radius
+                })
+                .collect();
+            let found = found(&grid, point, range);
+            let mut unique = found.clone();
+            unique.dedup();
+            assert_eq!(found, unique, "an entry was found twice");
+            for i in in_reach {
+                assert!(found.contains(&i), "missed entry {i} near {point}");
+            }
+        }
+    }
+
+    #[test]
+    fn clear_empties_the_grid() {
+        let mut grid = grid_of(&[(Vec2::ZERO, 200.0)]);
+        grid.clear();
+        assert!(found(&grid, Vec2::ZERO, 1000.0).is_empty());
+        // And forgets how big the entries were
+        grid.insert(Entity::from_raw(1), Vec2::new(500.0, 0.0), 1.0);
+        assert!(found(&grid, Vec2::new(300.0, 0.0), 1.0).is_empty());
+    }
+}
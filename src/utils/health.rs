@@ -3,5 +3,105 @@ use bevy::prelude::*;
 // Health is used in both healthbars.rs and tanks.rs, so it is defined here
 #[derive(Component)]
 pub struct Health {
-    pub value: u8,
-}
\ No newline at end of file
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Health {
+        Health { current: max, max }
+    }
+
+    // How full the health is. Can go above 1.0 when overhealed.
+    pub fn fraction(&self) -> f32 {
+        self.current / self.max
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.0
+    }
+
+    // Removes health, returning true only on the hit that kills, so that a death is reported once
+    // even if several bullets land on the same frame
+    pub fn damage(&mut self, amount: f32) -> bool {
+        let was_alive = !self.is_dead();
+        self.current = (self.current - amount).max(0.0);
+        was_alive && self.is_dead()
+    }
+
+    // Restores health up to the maximum. Health that is already overhealed is left alone.
+    pub fn heal(&mut self, amount: f32) {
+        if self.current < self.max {
+            self.current = (self.current + amount).min(self.max);
+        }
+    }
+
+    // Restores health, allowing it to go above the maximum up to `limit` times the maximum
+    pub fn overheal(&mut self, amount: f32, limit: f32) {
+        self.current = (self.current + amount).min(self.max * limit).max(self.current);
+    }
+}
+
+// A shield soaks up damage before it reaches the entity's Health. Once `recharge_delay` seconds have
+// passed without being hit, it recharges at `recharge_rate` per second.
+#[derive(Component)]
+pub struct Shield {
+    pub current: f32,
+    pub max: f32,
+    pub recharge_delay: f32,
+    pub recharge_rate: f32,
+    // Whether the most recent hit was (at least partly) stopped by the shield, so the hit can be
+    // shown differently
+    pub absorbed_last_hit: bool,
+}
+
+impl Shield {
+    pub fn new(max: f32, recharge_delay: f32, recharge_rate: f32) -> Shield {
+        Shield {
+            current: max,
+            max,
+            recharge_delay,
+            recharge_rate,
+            absorbed_last_hit: false,
+        }
+    }
+
+    pub fn fraction(&self) -> f32 {
+        self.current / self.max
+    }
+
+    // Takes as much of the damage as the shield can, returning what is left over for the health
+    pub fn absorb(&mut self, amount: f32) -> f32 {
+        let absorbed = amount.min(self.current);
+        self.current -= absorbed;
+        self.absorbed_last_hit = absorbed > 0.0;
+        amount - absorbed
+    }
+
+    pub fn recharge(&mut self, amount: f32) {
+        self.current = (self.current + amount).min(self.max);
+    }
+}
+
+// Damage is ignored while this is on an entity. The value is the time left in seconds.
+#[derive(Component)]
+pub struct Invulnerable(pub f32);
+
+// Sent when something with health runs out of it
+#[derive(Event)]
+pub struct Died {
+    pub entity: Entity,
+}
+
+pub fn update_invulnerability(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut invulnerables: Query<(Entity, &mut Invulnerable)>,
+) {
+    for (entity, mut invulnerable) in invulnerables.iter_mut() {
+        invulnerable.0 -= time.delta_seconds();
+        if invulnerable.0 <= 0.0 {
+            commands.entity(entity).remove::<Invulnerable>();
+        }
+    }
+}
@@ -1,4 +1,13 @@
 // Utils are for small pieces of code, such as individual components, that are required by mulitple modules
 
+mod angles;
+pub use angles::angle_between;
+
 mod health;
-pub use health::Health;
+pub use health::{update_invulnerability, Died, Health, Invulnerable, Shield};
+
+mod grid;
+pub use grid::{GridEntry, SpatialGrid};
+
+mod sweep;
+pub use sweep::{sweep_circle_circle, sweep_circle_rect};
@@ -0,0 +1,287 @@
+use bevy::prelude::*;
+
+// Swept collision tests, for things that move far enough in a tick to pass straight through what
+// they should have hit. A circle starting at `start` moves by `motion`, and the earliest point along
+// the way where it touches the other shape is reported.
+
+#[derive(Clone, Copy, Debug)]
+pub struct SweepHit {
+    // How far along the motion the hit happens, from 0.0 at the start to 1.0 at the end
+    pub time: f32,
+    // Where the moving circle's centre is when it hits
+    pub position: Vec2,
+    // Points away from the surface that was hit, towards the moving circle
+    pub normal: Vec2,
+}
+
+// A moving circle against a still one
+pub fn sweep_circle_circle(
+    start: Vec2,
+    motion: Vec2,
+    radius: f32,
+    centre: Vec2,
+    other_radius: f32,
+) -> Option<SweepHit> {
+    // The same as a point moving towards a circle as big as both of them put together
+    let reach = radius + other_radius;
+    let offset = start - centre;
+
+    // Already touching, so it is hit straight away unless it is moving away
+    if offset.length_squared() <= reach * reach {
+        let normal = offset
+            .try_normalize()
+            .unwrap_or(-motion.normalize_or_zero());
+        return (motion.dot(normal) < 0.0).then_some(SweepHit {
+            time: 0.0,
+            position: start,
+            normal,
+        });
+    }
+
+    // Solve |offset + motion * time| = reach for the first time it is true
+    let a = motion.length_squared();
+    if a == 0.0 {
+        return None;
+    }
+    let b = 2.0 * offset.dot(motion);
+    let c = offset.length_squared() - reach * reach;
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let time = (-b - discriminant.sqrt()) / (2.0 * a);
+    if !(0.0..=1.0).contains(&time) {
+        return None;
+    }
+    let position = start + motion * time;
+    Some(SweepHit {
+        time,
+        position,
+        normal: (position - centre) / reach,
+    })
+}
+
+// A moving circle against a solid, axis-aligned rectangle
+pub fn sweep_circle_rect(start: Vec2, motion: Vec2, radius: f32, rect: Rect) -> Option<SweepHit> {
+    // The circle's centre can't get closer than `radius` to the rectangle, so this is the same as a
+    // point against the rectangle grown by `radius`, with rounded corners. The straight sides are
+    // tested first, and the corners are tested as circles.
+    let grown = rect.inflate(radius);
+
+    // Already touching
+    let closest = start.clamp(rect.min, rect.max);
+    if start.distance_squared(closest) <= radius * radius {
+        let normal = (start - closest)
+            .try_normalize()
+            .unwrap_or_else(|| inside_normal(start, rect));
+        return (motion.dot(normal) < 0.0).then_some(SweepHit {
+            time: 0.0,
+            position: start,
+            normal,
+        });
+    }
+
+    // When the point is between both pairs of grown sides is when it is inside the grown rectangle
+    let mut enter = f32::NEG_INFINITY;
+    let mut exit = f32::INFINITY;
+    let mut normal = Vec2::ZERO;
+    for axis in 0..2 {
+        let (from, towards) = (start[axis], motion[axis]);
+        let (min, max) = (grown.min[axis], grown.max[axis]);
+        if towards == 0.0 {
+            if from < min || from > max {
+                return None;
+            }
+            continue;
+        }
+        let (near, far, side) = if towards > 0.0 {
+            ((min - from) / towards, (max - from) / towards, -1.0)
+        } else {
+            ((max - from) / towards, (min - from) / towards, 1.0)
+        };
+        if near > enter {
+            enter = near;
+            normal = Vec2::ZERO;
+            normal[axis] = side;
+        }
+        exit = exit.min(far);
+    }
+    if enter > exit || enter > 1.0 || exit < 0.0 {
+        return None;
+    }
+
+    // Coming in at a corner, where the grown rectangle is actually rounded
+    let position = start + motion * enter.max(0.0);
+    let corner = position.clamp(rect.min, rect.max);
+    let past_x = position.x < rect.min.x || position.x > rect.max.x;
+    let past_y = position.y < rect.min.y || position.y > rect.max.y;
+    if past_x && past_y {
+        return sweep_circle_circle(start, motion, radius, corner, 0.0);
+    }
+
+    (enter >= 0.0).then_some(SweepHit {
+        time: enter,
+        position,
+        normal,
+    })
+}
+
+// Which way out of the rectangle is quickest from a point inside it
+fn inside_normal(point: Vec2, rect: Rect) -> Vec2 {
+    let to_min = point - rect.min;
+    let to_max = rect.max - point;
+    let closest = to_min.min(to_max).min_element();
+    if closest == to_min.x {
+        Vec2::NEG_X
+    } else if closest == to_max.x {
+        Vec2::X
+    } else if closest == to_min.y {
+        Vec2::NEG_Y
+    } else {
+        Vec2::Y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_1_SQRT_2;
+
+    const RECT: Rect = Rect {
+        min: Vec2::new(100.0, -50.0),
+        max: Vec2::new(200.0, 50.0),
+    };
+
+    fn assert_close(a: Vec2, b: Vec2) {
+        assert!(a.abs_diff_eq(b, 1e-4), "{a} is not {b}");
+    }
+
+    fn assert_hit(hit: Option<SweepHit>, time: f32, position: Vec2, normal: Vec2) {
+        let hit = hit.expect("should hit");
+        assert!(
+            (hit.time - time).abs() < 1e-4,
+            "hit at {} not {time}",
+            hit.time
+        );
+        assert_close(hit.position, position);
+        assert_close(hit.normal, normal);
+    }
+
+    #[test]
+    fn circle_head_on() {
+        let hit = sweep_circle_circle(
+            Vec2::ZERO,
+            Vec2::new(100.0, 0.0),
+            5.0,
+            Vec2::new(50.0, 0.0),
+            10.0,
+        );
+        assert_hit(hit, 0.35, Vec2::new(35.0, 0.0), Vec2::NEG_X);
+    }
+
+    #[test]
+    fn circle_grazing_miss() {
+        let start = Vec2::new(0.0, 15.1);
+        let hit = sweep_circle_circle(
+            start,
+            Vec2::new(100.0, 0.0),
+            5.0,
+            Vec2::new(50.0, 0.0),
+            10.0,
+        );
+        assert!(hit.is_none());
+    }
+
+    #[test]
+    fn circle_out_of_reach() {
+        let centre = Vec2::new(50.0, 0.0);
+        // Stops short of it
+        assert!(sweep_circle_circle(Vec2::ZERO, Vec2::new(20.0, 0.0), 5.0, centre, 10.0).is_none());
+        // Moving away from it
+        assert!(
+            sweep_circle_circle(Vec2::ZERO, Vec2::new(-100.0, 0.0), 5.0, centre, 10.0).is_none()
+        );
+        // Not moving at all
+        assert!(sweep_circle_circle(Vec2::ZERO, Vec2::ZERO, 5.0, centre, 10.0).is_none());
+    }
+
+    #[test]
+    fn circle_already_touching() {
+        let start = Vec2::new(40.0, 0.0);
+        let centre = Vec2::new(50.0, 0.0);
+        let towards = sweep_circle_circle(start, Vec2::new(10.0, 0.0), 5.0, centre, 10.0);
+        assert_hit(towards, 0.0, start, Vec2::NEG_X);
+        // Touching things can still get away
+        assert!(sweep_circle_circle(start, Vec2::new(-10.0, 0.0), 5.0, centre, 10.0).is_none());
+        assert!(sweep_circle_circle(start, Vec2::ZERO, 5.0, centre, 10.0).is_none());
+    }
+
+    #[test]
+    fn rect_head_on() {
+        let hit = sweep_circle_rect(Vec2::ZERO, Vec2::new(200.0, 0.0), 5.0, RECT);
+        assert_hit(hit, 0.475, Vec2::new(95.0, 0.0), Vec2::NEG_X);
+
+        let from_above =
+            sweep_circle_rect(Vec2::new(150.0, 100.0), Vec2::new(0.0, -100.0), 5.0, RECT);
+        assert_hit(from_above, 0.45, Vec2::new(150.0, 55.0), Vec2::Y);
+    }
+
+    #[test]
+    fn rect_grazing_miss() {
+        let hit = sweep_circle_rect(Vec2::new(0.0, 55.1), Vec2::new(300.0, 0.0), 5.0, RECT);
+        assert!(hit.is_none());
+    }
+
+    #[test]
+    fn rect_out_of_reach() {
+        // It would only be reached past the end of the motion
+        assert!(sweep_circle_rect(Vec2::ZERO, Vec2::new(50.0, 0.0), 5.0, RECT).is_none());
+        // It was passed before the start of the motion
+        assert!(
+            sweep_circle_rect(Vec2::new(300.0, 0.0), Vec2::new(100.0, 0.0), 5.0, RECT).is_none()
+        );
+        assert!(sweep_circle_rect(Vec2::ZERO, Vec2::ZERO, 5.0, RECT).is_none());
+    }
+
+    #[test]
+    fn rect_corner_hit() {
+        // Heading straight for the top left corner, so it is hit on the rounded part of the grown
+        // rectangle rather than either of its sides
+        let hit = sweep_circle_rect(Vec2::new(80.0, 70.0), Vec2::new(40.0, -40.0), 5.0, RECT);
+        let normal = Vec2::new(-FRAC_1_SQRT_2, FRAC_1_SQRT_2);
+        let time = 0.5 - 5.0 * FRAC_1_SQRT_2 / 40.0;
+        assert_hit(hit, time, Vec2::new(100.0, 50.0) + normal * 5.0, normal);
+    }
+
+    #[test]
+    fn rect_corner_miss() {
+        // Passes through the square corner of the grown rectangle, but outside its rounded corner
+        let hit = sweep_circle_rect(Vec2::new(75.0, 35.0), Vec2::new(40.0, 40.0), 5.0, RECT);
+        assert!(hit.is_none());
+    }
+
+    #[test]
+    fn rect_already_touching() {
+        let start = Vec2::new(97.0, 0.0);
+        let towards = sweep_circle_rect(start, Vec2::new(10.0, 0.0), 5.0, RECT);
+        assert_hit(towards, 0.0, start, Vec2::NEG_X);
+        assert!(sweep_circle_rect(start, Vec2::new(-10.0, 0.0), 5.0, RECT).is_none());
+    }
+
+    #[test]
+    fn rect_starting_inside() {
+        // Pushed out of the nearest side, unless it is already heading out of it
+        let start = Vec2::new(110.0, 20.0);
+        let hit = sweep_circle_rect(start, Vec2::new(1.0, 0.0), 5.0, RECT);
+        assert_hit(hit, 0.0, start, Vec2::NEG_X);
+        assert!(sweep_circle_rect(start, Vec2::new(-1.0, 0.0), 5.0, RECT).is_none());
+    }
+
+    #[test]
+    fn inside_normal_picks_the_nearest_side() {
+        assert_eq!(inside_normal(Vec2::new(105.0, 0.0), RECT), Vec2::NEG_X);
+        assert_eq!(inside_normal(Vec2::new(195.0, 0.0), RECT), Vec2::X);
+        assert_eq!(inside_normal(Vec2::new(150.0, -45.0), RECT), Vec2::NEG_Y);
+        assert_eq!(inside_normal(Vec2::new(150.0, 45.0), RECT), Vec2::Y);
+    }
+}